@@ -0,0 +1,5 @@
+CREATE TABLE users (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL UNIQUE,
+    password_hash VARCHAR NOT NULL
+);
\ No newline at end of file
@@ -0,0 +1,6 @@
+CREATE TABLE invitations (
+    token VARCHAR PRIMARY KEY NOT NULL,
+    user_id BINARY(128) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
\ No newline at end of file
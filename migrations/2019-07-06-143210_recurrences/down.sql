@@ -0,0 +1 @@
+DROP TABLE recurrences;
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE recurrences (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    event_id BINARY(128) NOT NULL,
+    start TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    location_id BINARY(128) NOT NULL,
+    frequency VARCHAR NOT NULL,
+    until DATE,
+    count INTEGER,
+    exceptions VARCHAR NOT NULL,
+    FOREIGN KEY (event_id) REFERENCES events(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id)
+);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE pages;
\ No newline at end of file
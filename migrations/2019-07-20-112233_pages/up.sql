@@ -0,0 +1,7 @@
+CREATE TABLE pages (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    slug VARCHAR NOT NULL UNIQUE,
+    title VARCHAR NOT NULL,
+    body VARCHAR NOT NULL,
+    published BOOLEAN NOT NULL DEFAULT 0
+);
\ No newline at end of file
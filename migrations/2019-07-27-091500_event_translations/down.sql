@@ -0,0 +1 @@
+DROP TABLE event_translations;
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE event_translations (
+    event_id BINARY(128) NOT NULL,
+    language VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    PRIMARY KEY (event_id, language),
+    FOREIGN KEY (event_id) REFERENCES events(id)
+);
\ No newline at end of file
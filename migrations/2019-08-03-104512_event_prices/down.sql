@@ -0,0 +1,9 @@
+CREATE TABLE events_without_price (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL
+);
+INSERT INTO events_without_price SELECT id, title, teaser, description FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_price RENAME TO events;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN price VARCHAR;
\ No newline at end of file
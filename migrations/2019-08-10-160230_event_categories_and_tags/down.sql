@@ -0,0 +1,11 @@
+DROP TABLE tags;
+CREATE TABLE events_without_category (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR
+);
+INSERT INTO events_without_category SELECT id, title, teaser, description, price FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_category RENAME TO events;
\ No newline at end of file
@@ -0,0 +1,7 @@
+ALTER TABLE events ADD COLUMN category VARCHAR;
+CREATE TABLE tags (
+    event_id BINARY(128) NOT NULL,
+    name VARCHAR NOT NULL,
+    PRIMARY KEY (event_id, name),
+    FOREIGN KEY (event_id) REFERENCES events(id)
+);
\ No newline at end of file
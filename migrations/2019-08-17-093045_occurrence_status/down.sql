@@ -0,0 +1,12 @@
+CREATE TABLE occurrences_without_status (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    start TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    event_id BINARY(128) NOT NULL,
+    location_id BINARY(128) NOT NULL,
+    FOREIGN KEY (event_id) REFERENCES events(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id)
+);
+INSERT INTO occurrences_without_status SELECT id, start, duration, event_id, location_id FROM occurrences;
+DROP TABLE occurrences;
+ALTER TABLE occurrences_without_status RENAME TO occurrences;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE occurrences ADD COLUMN status VARCHAR NOT NULL DEFAULT 'scheduled';
+ALTER TABLE occurrences ADD COLUMN status_note VARCHAR NOT NULL DEFAULT '';
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE occurrences_without_overrides (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    start TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    event_id BINARY(128) NOT NULL,
+    location_id BINARY(128) NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'scheduled',
+    status_note VARCHAR NOT NULL DEFAULT '',
+    FOREIGN KEY (event_id) REFERENCES events(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id)
+);
+INSERT INTO occurrences_without_overrides SELECT id, start, duration, event_id, location_id, status, status_note FROM occurrences;
+DROP TABLE occurrences;
+ALTER TABLE occurrences_without_overrides RENAME TO occurrences;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE occurrences ADD COLUMN title_suffix VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE occurrences ADD COLUMN teaser_override VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE occurrences ADD COLUMN note VARCHAR NOT NULL DEFAULT '';
\ No newline at end of file
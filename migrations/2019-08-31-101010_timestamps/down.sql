@@ -0,0 +1,28 @@
+CREATE TABLE occurrences_without_timestamps (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    start TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    event_id BINARY(128) NOT NULL,
+    location_id BINARY(128) NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'scheduled',
+    status_note VARCHAR NOT NULL DEFAULT '',
+    title_suffix VARCHAR NOT NULL DEFAULT '',
+    teaser_override VARCHAR NOT NULL DEFAULT '',
+    note VARCHAR NOT NULL DEFAULT '',
+    FOREIGN KEY (event_id) REFERENCES events(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id)
+);
+INSERT INTO occurrences_without_timestamps SELECT id, start, duration, event_id, location_id, status, status_note, title_suffix, teaser_override, note FROM occurrences;
+DROP TABLE occurrences;
+ALTER TABLE occurrences_without_timestamps RENAME TO occurrences;
+CREATE TABLE events_without_timestamps (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR,
+    category VARCHAR
+);
+INSERT INTO events_without_timestamps SELECT id, title, teaser, description, price, category FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_timestamps RENAME TO events;
\ No newline at end of file
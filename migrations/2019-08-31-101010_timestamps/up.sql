@@ -0,0 +1,6 @@
+ALTER TABLE events ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE events ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+UPDATE events SET created_at = datetime('now', 'localtime'), updated_at = datetime('now', 'localtime');
+ALTER TABLE occurrences ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE occurrences ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+UPDATE occurrences SET created_at = datetime('now', 'localtime'), updated_at = datetime('now', 'localtime');
\ No newline at end of file
@@ -0,0 +1,50 @@
+CREATE TABLE pages_without_authors (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    slug VARCHAR NOT NULL UNIQUE,
+    title VARCHAR NOT NULL,
+    body VARCHAR NOT NULL,
+    published BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO pages_without_authors SELECT id, slug, title, body, published FROM pages;
+DROP TABLE pages;
+ALTER TABLE pages_without_authors RENAME TO pages;
+CREATE TABLE locations_without_authors (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL,
+    address VARCHAR NOT NULL
+);
+INSERT INTO locations_without_authors SELECT id, name, address FROM locations;
+DROP TABLE locations;
+ALTER TABLE locations_without_authors RENAME TO locations;
+CREATE TABLE occurrences_without_authors (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    start TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    event_id BINARY(128) NOT NULL,
+    location_id BINARY(128) NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'scheduled',
+    status_note VARCHAR NOT NULL DEFAULT '',
+    title_suffix VARCHAR NOT NULL DEFAULT '',
+    teaser_override VARCHAR NOT NULL DEFAULT '',
+    note VARCHAR NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    FOREIGN KEY (event_id) REFERENCES events(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id)
+);
+INSERT INTO occurrences_without_authors SELECT id, start, duration, event_id, location_id, status, status_note, title_suffix, teaser_override, note, created_at, updated_at FROM occurrences;
+DROP TABLE occurrences;
+ALTER TABLE occurrences_without_authors RENAME TO occurrences;
+CREATE TABLE events_without_authors (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR,
+    category VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00'
+);
+INSERT INTO events_without_authors SELECT id, title, teaser, description, price, category, created_at, updated_at FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_authors RENAME TO events;
\ No newline at end of file
@@ -0,0 +1,10 @@
+ALTER TABLE events ADD COLUMN updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE occurrences ADD COLUMN updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE locations ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE locations ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE locations ADD COLUMN updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL;
+UPDATE locations SET created_at = datetime('now', 'localtime'), updated_at = datetime('now', 'localtime');
+ALTER TABLE pages ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE pages ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE pages ADD COLUMN updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL;
+UPDATE pages SET created_at = datetime('now', 'localtime'), updated_at = datetime('now', 'localtime');
\ No newline at end of file
@@ -0,0 +1,10 @@
+CREATE TABLE revisions (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    item_kind VARCHAR NOT NULL,
+    item_id BINARY(128) NOT NULL,
+    action VARCHAR NOT NULL,
+    snapshot VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    created_by BINARY(128),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
\ No newline at end of file
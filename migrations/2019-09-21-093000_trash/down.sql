@@ -0,0 +1,25 @@
+CREATE TABLE locations_without_trash (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL
+);
+INSERT INTO locations_without_trash SELECT id, name, address, created_at, updated_at, updated_by FROM locations;
+DROP TABLE locations;
+ALTER TABLE locations_without_trash RENAME TO locations;
+CREATE TABLE events_without_trash (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR,
+    category VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL
+);
+INSERT INTO events_without_trash SELECT id, title, teaser, description, price, category, created_at, updated_at, updated_by FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_trash RENAME TO events;
\ No newline at end of file
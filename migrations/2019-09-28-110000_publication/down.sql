@@ -0,0 +1,15 @@
+CREATE TABLE events_without_publication (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR,
+    category VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL,
+    deleted_at TIMESTAMP
+);
+INSERT INTO events_without_publication SELECT id, title, teaser, description, price, category, created_at, updated_at, updated_by, deleted_at FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_publication RENAME TO events;
\ No newline at end of file
@@ -0,0 +1,4 @@
+ALTER TABLE events ADD COLUMN publication VARCHAR NOT NULL DEFAULT 'published';
+ALTER TABLE events ADD COLUMN publish_at TIMESTAMP;
+ALTER TABLE events ADD COLUMN preview_token VARCHAR NOT NULL DEFAULT '';
+UPDATE events SET preview_token = lower(hex(randomblob(16)));
\ No newline at end of file
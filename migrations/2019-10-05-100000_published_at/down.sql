@@ -0,0 +1,18 @@
+CREATE TABLE events_without_published_at (
+    id BINARY(128) PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    teaser VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    price VARCHAR,
+    category VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    updated_by BINARY(128) REFERENCES users(id) ON DELETE SET NULL,
+    deleted_at TIMESTAMP,
+    publication VARCHAR NOT NULL DEFAULT 'published',
+    publish_at TIMESTAMP,
+    preview_token VARCHAR NOT NULL DEFAULT ''
+);
+INSERT INTO events_without_published_at SELECT id, title, teaser, description, price, category, created_at, updated_at, updated_by, deleted_at, publication, publish_at, preview_token FROM events;
+DROP TABLE events;
+ALTER TABLE events_without_published_at RENAME TO events;
@@ -0,0 +1,3 @@
+ALTER TABLE events ADD COLUMN published_at TIMESTAMP;
+UPDATE events SET published_at = created_at WHERE publication = 'published';
+UPDATE events SET published_at = publish_at WHERE publication = 'scheduled';
@@ -1,49 +1,324 @@
 use std::collections::HashMap;
+use std::io::Cursor;
 
-use rocket::Rocket;
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::{Rocket, State};
 use rocket_contrib::json::Json;
+use serde::Serialize;
 
+use crate::auth::Admin;
 use crate::store::{
-    Id, Location, LocationWithOccurrences, OccurrenceFilter, OccurrenceFilterError, Overview, Store,
+    Event, Id, Location, LocationDeletionError, LocationWithOccurrences, OccurrenceFilter,
+    OccurrenceFilterError, Overview, Revision, Store, ValidationError, ValidationErrors,
 };
 
 pub fn mount(rocket: Rocket, prefix: &'static str) -> Rocket {
     rocket
+        .manage(ApiPrefix(prefix))
+        .register(catchers![
+            bad_request,
+            unauthorized,
+            not_found,
+            unprocessable,
+            internal
+        ])
         .mount(
             prefix,
             routes![api_overview, api_locations_with_occurrences],
         )
         .mount(&format!("{}/locations", prefix), locations::routes())
         .mount(&format!("{}/events", prefix), events::routes())
+        .mount(&format!("{}/pages", prefix), pages::routes())
+        .mount(&format!("{}/users", prefix), users::routes())
+        .mount(&format!("{}/import", prefix), import::routes())
+        .mount(&format!("{}/trash", prefix), trash::routes())
+}
+
+/// The errors returned by all API routes. They are sent as JSON, e. g.
+/// `{ "error": "not_found", "message": "The requested item does not exist." }`.
+/// Invalid items additionally list the messages for each field, e. g.
+/// `"fields": { "event.title": ["Darf nicht leer sein."] }`.
+/// Locations that are still in use list the affected `events`.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    Conflict(String),
+    LocationInUse(HashMap<Id<Event>, Event>),
+    Unprocessable(String),
+    Invalid(ValidationErrors),
+    TooLarge(String),
+    /// The details are only logged, as they may reveal the database's internals.
+    Internal,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<ValidationErrors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Option<HashMap<Id<Event>, Event>>,
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        let mut fields = None;
+        let mut events = None;
+        let (status, error, message) = match self {
+            ApiError::BadRequest => (
+                Status::BadRequest,
+                "bad_request",
+                "The request is malformed.".to_string(),
+            ),
+            ApiError::Unauthorized => (
+                Status::Unauthorized,
+                "unauthorized",
+                "Only logged in users may do this.".to_string(),
+            ),
+            ApiError::NotFound => (
+                Status::NotFound,
+                "not_found",
+                "The requested item does not exist.".to_string(),
+            ),
+            ApiError::Conflict(message) => (Status::Conflict, "conflict", message),
+            ApiError::LocationInUse(affected) => {
+                events = Some(affected);
+                (
+                    Status::Conflict,
+                    "location_in_use",
+                    "Events still take place at this location. Reassign or delete them first."
+                        .to_string(),
+                )
+            }
+            ApiError::Unprocessable(message) => {
+                (Status::UnprocessableEntity, "unprocessable", message)
+            }
+            ApiError::Invalid(errors) => {
+                fields = Some(errors);
+                (
+                    Status::UnprocessableEntity,
+                    "invalid",
+                    "Some fields are invalid.".to_string(),
+                )
+            }
+            ApiError::TooLarge(message) => (Status::PayloadTooLarge, "too_large", message),
+            ApiError::Internal => (
+                Status::InternalServerError,
+                "internal",
+                "Something went wrong on the server.".to_string(),
+            ),
+        };
+        let body = ErrorBody {
+            error,
+            message,
+            fields,
+            events,
+        };
+
+        Response::build()
+            .header(ContentType::JSON)
+            .sized_body(Cursor::new(serde_json::to_string(&body).unwrap()))
+            .status(status)
+            .ok()
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(err: diesel::result::Error) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        match err {
+            Error::NotFound => ApiError::NotFound,
+            Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info)
+            | Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                ApiError::Conflict(info.message().to_string())
+            }
+            other => {
+                eprintln!("Database error: {}", other);
+                ApiError::Internal
+            }
+        }
+    }
+}
+
+impl From<LocationDeletionError> for ApiError {
+    fn from(err: LocationDeletionError) -> Self {
+        match err {
+            LocationDeletionError::InUse(events) => ApiError::LocationInUse(events),
+            LocationDeletionError::InvalidTarget => ApiError::Unprocessable(
+                "The location to reassign to must be another existing location.".to_string(),
+            ),
+            LocationDeletionError::Database(err) => err.into(),
+        }
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(err: ValidationError) -> Self {
+        match err {
+            ValidationError::Invalid(errors) => ApiError::Invalid(errors),
+            ValidationError::Database(err) => err.into(),
+        }
+    }
+}
+
+impl From<OccurrenceFilterError> for ApiError {
+    fn from(err: OccurrenceFilterError) -> Self {
+        use OccurrenceFilterError::*;
+
+        let message = match err {
+            InvalidBeforeDate => "The 'before' date is invalid.",
+            InvalidAfterDate => "The 'after' date is invalid.",
+            InvalidRange => "The 'after' date must not be later than the 'before' date.",
+            InvalidCategory => "The category is unknown.",
+        };
+        ApiError::Unprocessable(message.to_string())
+    }
+}
+
+/// The path the API is mounted at.
+struct ApiPrefix(&'static str);
+
+/// Catchers apply to all routes, so only errors of the API are sent as JSON.
+/// The pages get a short HTML error page, like Rocket's default one.
+enum Caught {
+    Api(ApiError),
+    Page(Status),
+}
+
+impl Caught {
+    fn new(req: &Request, error: ApiError, status: Status) -> Self {
+        let is_api = req
+            .guard::<State<ApiPrefix>>()
+            .succeeded()
+            .map_or(false, |prefix| {
+                let path = req.uri().path();
+                path == prefix.0 || path.starts_with(&format!("{}/", prefix.0))
+            });
+
+        if is_api {
+            Caught::Api(error)
+        } else {
+            Caught::Page(status)
+        }
+    }
+}
+
+impl<'r> Responder<'r> for Caught {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        match self {
+            Caught::Api(error) => error.respond_to(req),
+            Caught::Page(status) => {
+                let title = format!("{} {}", status.code, status.reason);
+                let body = format!(
+                    "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title></head>\
+                     <body><h1>{0}</h1></body></html>",
+                    title
+                );
+
+                Response::build()
+                    .header(ContentType::HTML)
+                    .sized_body(Cursor::new(body))
+                    .status(status)
+                    .ok()
+            }
+        }
+    }
+}
+
+#[catch(400)]
+fn bad_request(req: &Request) -> Caught {
+    Caught::new(req, ApiError::BadRequest, Status::BadRequest)
+}
+
+#[catch(401)]
+fn unauthorized(req: &Request) -> Caught {
+    Caught::new(req, ApiError::Unauthorized, Status::Unauthorized)
+}
+
+#[catch(404)]
+fn not_found(req: &Request) -> Caught {
+    Caught::new(req, ApiError::NotFound, Status::NotFound)
+}
+
+/// Rocket fails with this status if the body cannot be parsed, e. g. when a field is missing.
+#[catch(422)]
+fn unprocessable(req: &Request) -> Caught {
+    let error = ApiError::Unprocessable("The request body is invalid.".to_string());
+    Caught::new(req, error, Status::UnprocessableEntity)
+}
+
+#[catch(500)]
+fn internal(req: &Request) -> Caught {
+    Caught::new(req, ApiError::Internal, Status::InternalServerError)
+}
+
+type FilterResult = Result<OccurrenceFilter, OccurrenceFilterError>;
+
+/// The filter of a read route. Only admins also see the events that are not published yet.
+fn visible_filter(
+    filter: FilterResult,
+    admin: &Option<Admin>,
+) -> Result<OccurrenceFilter, ApiError> {
+    Ok(OccurrenceFilter {
+        unpublished: admin.is_some(),
+        ..filter?
+    })
+}
+
+/// An entry of an item's history, which can be restored by its id.
+#[derive(Serialize)]
+struct ListedRevision {
+    id: Id<Revision>,
+    #[serde(flatten)]
+    revision: Revision,
+}
+
+fn list_revisions(history: Vec<(Id<Revision>, Revision)>) -> Vec<ListedRevision> {
+    history
+        .into_iter()
+        .map(|(id, revision)| ListedRevision { id, revision })
+        .collect()
 }
 
 #[get("/?<filter..>")]
 fn api_overview(
     store: Store,
-    filter: OccurrenceFilter,
-) -> Result<Json<Overview>, OccurrenceFilterError> {
-    Ok(Json(store.read_all(&filter)))
+    admin: Option<Admin>,
+    filter: FilterResult,
+) -> Result<Json<Overview>, ApiError> {
+    Ok(Json(store.read_all(&visible_filter(filter, &admin)?)))
 }
 
 #[get("/locations_with_occurrences?<filter..>")]
 fn api_locations_with_occurrences(
     store: Store,
-    filter: OccurrenceFilter,
-) -> Result<Json<HashMap<Id<Location>, LocationWithOccurrences>>, OccurrenceFilterError> {
-    Ok(Json(store.locations_with_occurrences(&filter)))
+    admin: Option<Admin>,
+    filter: FilterResult,
+) -> Result<Json<HashMap<Id<Location>, LocationWithOccurrences>>, ApiError> {
+    Ok(Json(store.locations_with_occurrences(&visible_filter(
+        filter, &admin,
+    )?)))
 }
 
 mod locations {
     use std::collections::HashMap;
     use std::iter::FromIterator;
 
-    use crate::store::Actions;
-    use crate::store::{Id, Location, Store};
+    use super::{list_revisions, ApiError, ListedRevision};
+    use crate::auth::Admin;
+    use crate::store::{Actions, Validate};
+    use crate::store::{Id, Location, LocationDeletion, Revision, Store};
 
     use rocket::Route;
     use rocket_contrib::json::Json;
 
-    type Result<T> = std::result::Result<T, String>;
+    type Result<T> = std::result::Result<T, ApiError>;
 
     #[get("/")]
     fn all(store: Store) -> Json<HashMap<Id<Location>, Location>> {
@@ -51,98 +326,871 @@ mod locations {
     }
 
     #[post("/", data = "<obj>")]
-    fn create(store: Store, obj: Json<Location>) -> Result<Json<Id<Location>>> {
-        store.create(obj.0).map_err(|err| err.to_string()).map(Json)
+    fn create(store: Store, _admin: Admin, obj: Json<Location>) -> Result<Json<Id<Location>>> {
+        obj.validate(&store)?;
+        store.create(obj.0).map_err(ApiError::from).map(Json)
     }
 
     #[get("/<id>")]
     fn read(store: Store, id: Id<Location>) -> Result<Json<Location>> {
-        store.read(id).map_err(|err| err.to_string()).map(Json)
+        store.read(id).map_err(ApiError::from).map(Json)
     }
 
     #[put("/<id>", data = "<obj>")]
-    pub fn update(store: Store, id: Id<Location>, obj: Json<Location>) -> Result<Json<Location>> {
+    pub fn update(
+        store: Store,
+        _admin: Admin,
+        id: Id<Location>,
+        obj: Json<Location>,
+    ) -> Result<Json<Location>> {
+        obj.validate(&store)?;
+        store.update(id, obj.0).map_err(ApiError::from).map(Json)
+    }
+
+    /// Refuses to delete a location where events take place, unless their occurrences
+    /// are either moved to another location or deleted as well.
+    #[delete("/<id>?<reassign_to>&<cascade>")]
+    fn delete(
+        store: Store,
+        _admin: Admin,
+        id: Id<Location>,
+        reassign_to: Option<Id<Location>>,
+        cascade: Option<bool>,
+    ) -> Result<Json<Location>> {
+        let deletion = match (reassign_to, cascade.unwrap_or(false)) {
+            (Some(_), true) => {
+                return Err(ApiError::Unprocessable(
+                    "Either reassign or cascade, not both.".to_string(),
+                ))
+            }
+            (Some(target), false) => LocationDeletion::ReassignTo(target),
+            (None, true) => LocationDeletion::Cascade,
+            (None, false) => LocationDeletion::Refuse,
+        };
+
         store
-            .update(id, obj.0)
-            .map_err(|err| err.to_string())
+            .delete_location(id, deletion)
+            .map_err(ApiError::from)
             .map(Json)
     }
 
-    #[delete("/<id>")]
-    fn delete(store: Store, id: Id<Location>) -> Result<Json<Location>> {
+    /// The previous versions of the location, the newest first. Deleted locations have one, too.
+    #[get("/<id>/history")]
+    fn history(store: Store, _admin: Admin, id: Id<Location>) -> Result<Json<Vec<ListedRevision>>> {
+        store
+            .location_history(id)
+            .map_err(ApiError::from)
+            .map(|history| Json(list_revisions(history)))
+    }
+
+    /// Changes the location back to a previous version, or restores it if it was deleted.
+    #[post("/<id>/history/<revision_id>/restore")]
+    fn restore(
+        store: Store,
+        _admin: Admin,
+        id: Id<Location>,
+        revision_id: Id<Revision>,
+    ) -> Result<Json<Location>> {
         store
-            .delete(id)
-            .map_err(|err| format!("{:?}", err))
+            .restore_location(id, revision_id)
+            .map_err(ApiError::from)
             .map(Json)
     }
 
     pub fn routes() -> Vec<Route> {
-        routes![all, create, read, update, delete]
+        routes![all, create, read, update, delete, history, restore]
     }
 }
 
 mod events {
     use std::collections::HashMap;
-    use std::iter::FromIterator;
 
+    use super::{list_revisions, visible_filter, ApiError, FilterResult, ListedRevision};
+    use crate::auth::Admin;
+    use crate::calendar;
+    use crate::markdown;
     use crate::store::{
-        Event, EventWithOccurrences, Id, OccurrenceFilter, OccurrenceFilterError, Store,
+        Actions, Event, EventWithOccurrences, Id, Location, Occurrence, OccurrenceStatus,
+        OccurrenceWithLocation, Revision, Store, Validate,
     };
 
+    use chrono::NaiveDateTime;
+    use rocket::response::content::Content;
     use rocket::Route;
     use rocket_contrib::json::Json;
+    use serde::Serialize;
+
+    type Result<T> = std::result::Result<T, ApiError>;
+
+    /// The event's Markdown fields rendered to HTML, e. g. for previews in the admin.
+    #[derive(Serialize)]
+    struct EventHtml {
+        teaser: String,
+        description: String,
+    }
+
+    impl EventHtml {
+        fn from_event(event: &Event) -> Self {
+            EventHtml {
+                teaser: markdown::render(&event.teaser),
+                description: markdown::render(&event.description),
+            }
+        }
+    }
+
+    /// Adds the rendered HTML if it was requested with `?html=true`.
+    #[derive(Serialize)]
+    struct WithHtml {
+        #[serde(flatten)]
+        item: EventWithOccurrences,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        html: Option<EventHtml>,
+    }
+
+    impl WithHtml {
+        fn new(item: EventWithOccurrences, html: Option<bool>) -> Self {
+            let html = if html.unwrap_or(false) {
+                Some(EventHtml::from_event(&item.event))
+            } else {
+                None
+            };
+
+            WithHtml { item, html }
+        }
+    }
+
+    /// The orders in which events can be listed.
+    #[derive(FromFormValue)]
+    enum Sort {
+        #[form(value = "next_occurrence")]
+        NextOccurrence,
+    }
+
+    /// The link that shows the event on the website before it is published.
+    #[derive(Serialize)]
+    struct Preview {
+        token: String,
+        path: String,
+    }
 
-    #[get("/?<filter..>")]
+    /// Only admins find the events that are not published yet.
+    fn check_visible(store: &Store, admin: &Option<Admin>, id: &Id<Event>) -> Result<()> {
+        if admin.is_some() || store.event_is_published(id.clone())? {
+            Ok(())
+        } else {
+            Err(ApiError::NotFound)
+        }
+    }
+
+    #[derive(Serialize)]
+    struct ListedEvent {
+        id: Id<Event>,
+        #[serde(flatten)]
+        item: WithHtml,
+        next_occurrence: Option<NaiveDateTime>,
+    }
+
+    /// Lists the events in order, e. g. `?sort=next_occurrence&limit=10&offset=20`.
+    /// Without `sort`, the events are returned by id instead.
+    #[get("/?<sort>&<limit>&<offset>&<html>&<filter..>")]
+    fn sorted(
+        store: Store,
+        admin: Option<Admin>,
+        sort: Sort,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        html: Option<bool>,
+        filter: FilterResult,
+    ) -> Result<Json<Vec<ListedEvent>>> {
+        let events = match sort {
+            Sort::NextOccurrence => {
+                store.events_by_next_occurrence(&visible_filter(filter, &admin)?)
+            }
+        };
+
+        Ok(Json(
+            events
+                .into_iter()
+                .skip(offset.unwrap_or(0))
+                .take(limit.unwrap_or(std::usize::MAX))
+                .map(|(id, item)| {
+                    let next_occurrence = item
+                        .next_occurrence()
+                        .map(|occurrence| occurrence.occurrence.start);
+
+                    ListedEvent {
+                        id,
+                        item: WithHtml::new(item, html),
+                        next_occurrence,
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Paging only works on sorted events, so `limit` and `offset` are refused without `sort`.
+    /// This route also receives unknown values of `sort`.
+    #[get("/?<sort>&<limit>&<offset>&<html>&<filter..>", rank = 2)]
     fn all(
         store: Store,
-        filter: OccurrenceFilter,
-    ) -> Result<Json<HashMap<Id<Event>, EventWithOccurrences>>, OccurrenceFilterError> {
-        Ok(Json(HashMap::from_iter(
-            store.all_events_with_occurrences(&filter),
-        )))
+        admin: Option<Admin>,
+        sort: Option<String>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        html: Option<bool>,
+        filter: FilterResult,
+    ) -> Result<Json<HashMap<Id<Event>, WithHtml>>> {
+        if let Some(sort) = sort {
+            return Err(ApiError::Unprocessable(format!(
+                "The events cannot be sorted by '{}'.",
+                sort
+            )));
+        }
+        if limit.is_some() || offset.is_some() {
+            return Err(ApiError::Unprocessable(
+                "The events can only be paged with 'sort'.".to_string(),
+            ));
+        }
+
+        Ok(Json(
+            store
+                .all_events_with_occurrences(&visible_filter(filter, &admin)?)
+                .into_iter()
+                .map(|(id, item)| (id, WithHtml::new(item, html)))
+                .collect(),
+        ))
     }
 
     #[post("/", data = "<obj>")]
-    fn create(store: Store, obj: Json<EventWithOccurrences>) -> Result<Json<Id<Event>>, String> {
+    fn create(
+        store: Store,
+        _admin: Admin,
+        obj: Json<EventWithOccurrences>,
+    ) -> Result<Json<Id<Event>>> {
+        obj.validate(&store)?;
         store
             .create_event_with_occurrences(obj.0)
-            .map_err(|err| err.to_string())
+            .map_err(ApiError::from)
             .map(Json)
     }
 
-    #[get("/<id>?<filter..>")]
+    #[get("/<id>?<html>&<filter..>")]
     fn read(
         store: Store,
+        admin: Option<Admin>,
         id: Id<Event>,
-        filter: OccurrenceFilter,
-    ) -> Result<Json<EventWithOccurrences>, OccurrenceFilterError> {
-        Ok(Json(
-            store.read_event_with_occurrences(id, &filter).unwrap(),
-        ))
+        html: Option<bool>,
+        filter: FilterResult,
+    ) -> Result<Json<WithHtml>> {
+        check_visible(&store, &admin, &id)?;
+        store
+            .read_event_with_occurrences(id, &filter?)
+            .map_err(ApiError::from)
+            .map(|item| Json(WithHtml::new(item, html)))
     }
 
     #[put("/<id>?<filter..>", data = "<obj>")]
     fn update(
         store: Store,
+        _admin: Admin,
         id: Id<Event>,
         obj: Json<EventWithOccurrences>,
-        filter: OccurrenceFilter,
-    ) -> Result<Json<EventWithOccurrences>, OccurrenceFilterError> {
-        Ok(Json(
-            store
-                .update_event_with_occurrences(id, obj.0, &filter)
-                .unwrap(),
-        ))
+        filter: FilterResult,
+    ) -> Result<Json<EventWithOccurrences>> {
+        obj.validate(&store)?;
+        store
+            .update_event_with_occurrences(id, obj.0, &filter?)
+            .map_err(ApiError::from)
+            .map(Json)
     }
 
     #[delete("/<id>")]
-    fn delete(store: Store, id: Id<Event>) -> Result<Json<EventWithOccurrences>, String> {
+    fn delete(store: Store, _admin: Admin, id: Id<Event>) -> Result<Json<EventWithOccurrences>> {
         store
             .delete_event_with_occurrences(id)
-            .map_err(|err| format!("{:?}", err))
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    #[get("/<id>/calendar.ics?<filter..>")]
+    fn event_calendar(
+        store: Store,
+        admin: Option<Admin>,
+        id: Id<Event>,
+        filter: FilterResult,
+    ) -> Result<Content<String>> {
+        check_visible(&store, &admin, &id)?;
+        let locations: HashMap<Id<Location>, Location> = store.all();
+
+        store
+            .event_occurrences(id, &visible_filter(filter, &admin)?)
+            .map_err(ApiError::from)
+            .map(|occurrences| calendar::render(&occurrences, &locations))
+    }
+
+    #[get("/<id>/occurrences/<occurrence_id>")]
+    fn read_occurrence(
+        store: Store,
+        admin: Option<Admin>,
+        id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+    ) -> Result<Json<OccurrenceWithLocation>> {
+        check_visible(&store, &admin, &id)?;
+        store
+            .read_occurrence(id, occurrence_id)
+            .map_err(ApiError::from)
             .map(Json)
     }
+
+    #[put("/<id>/occurrences/<occurrence_id>", data = "<obj>")]
+    fn update_occurrence(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+        obj: Json<OccurrenceWithLocation>,
+    ) -> Result<Json<OccurrenceWithLocation>> {
+        obj.validate(&store)?;
+        store
+            .update_occurrence(id, occurrence_id, obj.0)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    #[delete("/<id>/occurrences/<occurrence_id>")]
+    fn delete_occurrence(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+    ) -> Result<Json<OccurrenceWithLocation>> {
+        store
+            .delete_occurrence(id, occurrence_id)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    /// Marks the occurrence as e. g. cancelled. Returns the previous status.
+    #[put("/<id>/occurrences/<occurrence_id>/status", data = "<obj>")]
+    fn set_occurrence_status(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+        obj: Json<OccurrenceStatus>,
+    ) -> Result<Json<OccurrenceStatus>> {
+        store
+            .set_occurrence_status(id, occurrence_id, obj.0)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    /// The previous versions of the event with all its occurrences, the newest first. Deleted
+    /// events have one, too.
+    #[get("/<id>/history")]
+    fn history(store: Store, _admin: Admin, id: Id<Event>) -> Result<Json<Vec<ListedRevision>>> {
+        store
+            .event_history(id)
+            .map_err(ApiError::from)
+            .map(|history| Json(list_revisions(history)))
+    }
+
+    /// Changes the event back to a previous version, or restores it with its occurrences if it
+    /// was deleted.
+    #[post("/<id>/history/<revision_id>/restore")]
+    fn restore(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+        revision_id: Id<Revision>,
+    ) -> Result<Json<EventWithOccurrences>> {
+        store
+            .restore_event(id, revision_id)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    /// The preview link to share the event before it is published, e. g.
+    /// `{ "token": "…", "path": "/events/<id>/preview?token=…" }`.
+    #[get("/<id>/preview")]
+    fn preview(store: Store, _admin: Admin, id: Id<Event>) -> Result<Json<Preview>> {
+        let token = store.preview_token(id.clone())?;
+        let path = format!("/events/{}/preview?token={}", id, token);
+
+        Ok(Json(Preview { token, path }))
+    }
+
+    pub fn routes() -> Vec<Route> {
+        routes![
+            sorted,
+            all,
+            create,
+            read,
+            update,
+            delete,
+            event_calendar,
+            preview,
+            read_occurrence,
+            update_occurrence,
+            delete_occurrence,
+            set_occurrence_status,
+            history,
+            restore
+        ]
+    }
+}
+
+mod pages {
+    use std::collections::HashMap;
+    use std::iter::FromIterator;
+
+    use super::ApiError;
+    use crate::auth::Admin;
+    use crate::store::{Actions, Validate};
+    use crate::store::{Id, Page, Store};
+
+    use rocket::Route;
+    use rocket_contrib::json::Json;
+
+    type Result<T> = std::result::Result<T, ApiError>;
+
+    #[get("/")]
+    fn all(store: Store, _admin: Admin) -> Json<HashMap<Id<Page>, Page>> {
+        Json(HashMap::from_iter(store.all()))
+    }
+
+    #[post("/", data = "<obj>")]
+    fn create(store: Store, _admin: Admin, obj: Json<Page>) -> Result<Json<Id<Page>>> {
+        obj.validate(&store)?;
+        store.create(obj.0).map_err(ApiError::from).map(Json)
+    }
+
+    #[get("/<id>")]
+    fn read(store: Store, _admin: Admin, id: Id<Page>) -> Result<Json<Page>> {
+        store.read(id).map_err(ApiError::from).map(Json)
+    }
+
+    #[put("/<id>", data = "<obj>")]
+    fn update(store: Store, _admin: Admin, id: Id<Page>, obj: Json<Page>) -> Result<Json<Page>> {
+        obj.validate(&store)?;
+        store.update(id, obj.0).map_err(ApiError::from).map(Json)
+    }
+
+    #[delete("/<id>")]
+    fn delete(store: Store, _admin: Admin, id: Id<Page>) -> Result<Json<Page>> {
+        store.delete(id).map_err(ApiError::from).map(Json)
+    }
+
     pub fn routes() -> Vec<Route> {
         routes![all, create, read, update, delete]
     }
 }
+
+mod users {
+    use std::collections::HashMap;
+    use std::iter::FromIterator;
+
+    use super::ApiError;
+    use crate::auth::Admin;
+    use crate::store::{Acceptance, Id, Invitation, Store, User};
+    use crate::store::{Actions, Validate};
+
+    use rocket::Route;
+    use rocket_contrib::json::Json;
+
+    type Result<T> = std::result::Result<T, ApiError>;
+
+    #[get("/")]
+    fn all(store: Store, _admin: Admin) -> Json<HashMap<Id<User>, User>> {
+        Json(HashMap::from_iter(store.all()))
+    }
+
+    #[post("/", data = "<obj>")]
+    fn create(store: Store, _admin: Admin, obj: Json<User>) -> Result<Json<Id<User>>> {
+        obj.validate(&store)?;
+        store.create(obj.0).map_err(ApiError::from).map(Json)
+    }
+
+    #[get("/<id>")]
+    fn read(store: Store, _admin: Admin, id: Id<User>) -> Result<Json<User>> {
+        store.read(id).map_err(ApiError::from).map(Json)
+    }
+
+    #[put("/<id>", data = "<obj>")]
+    fn update(store: Store, _admin: Admin, id: Id<User>, obj: Json<User>) -> Result<Json<User>> {
+        obj.validate(&store)?;
+        store.update(id, obj.0).map_err(ApiError::from).map(Json)
+    }
+
+    #[delete("/<id>")]
+    fn delete(store: Store, admin: Admin, id: Id<User>) -> Result<Json<User>> {
+        if admin.0 == id {
+            return Err(ApiError::Conflict(
+                "Admins cannot revoke themselves.".to_string(),
+            ));
+        }
+
+        store.delete(id).map_err(ApiError::from).map(Json)
+    }
+
+    #[post("/<id>/invitation")]
+    fn invite(store: Store, _admin: Admin, id: Id<User>) -> Result<Json<Invitation>> {
+        store
+            .create_invitation(id)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    #[post("/invitations", data = "<obj>")]
+    fn accept(store: Store, obj: Json<Acceptance>) -> Result<Json<Id<User>>> {
+        obj.validate(&store)?;
+        store
+            .accept_invitation(&obj)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    pub fn routes() -> Vec<Route> {
+        routes![all, create, read, update, delete, invite, accept]
+    }
+}
+
+mod trash {
+    use super::ApiError;
+    use crate::auth::Admin;
+    use crate::store::{Event, EventWithOccurrences, Id, Location, Store, Trash};
+
+    use rocket::Route;
+    use rocket_contrib::json::Json;
+
+    type Result<T> = std::result::Result<T, ApiError>;
+
+    /// The deleted events and locations with when they were deleted, e. g.
+    /// `{ "events": { "…": { "deleted_at": "…", "event": { … }, … } }, "locations": { … } }`.
+    #[get("/")]
+    fn all(store: Store, _admin: Admin) -> Result<Json<Trash>> {
+        store.trash().map_err(ApiError::from).map(Json)
+    }
+
+    /// Also restores the trashed locations where the event takes place.
+    #[post("/events/<id>/restore")]
+    fn restore_event(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+    ) -> Result<Json<EventWithOccurrences>> {
+        store
+            .restore_trashed_event(id)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    #[delete("/events/<id>")]
+    fn purge_event(
+        store: Store,
+        _admin: Admin,
+        id: Id<Event>,
+    ) -> Result<Json<EventWithOccurrences>> {
+        store.purge_event(id).map_err(ApiError::from).map(Json)
+    }
+
+    #[post("/locations/<id>/restore")]
+    fn restore_location(store: Store, _admin: Admin, id: Id<Location>) -> Result<Json<Location>> {
+        store
+            .restore_trashed_location(id)
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    /// Fails with a conflict while trashed events still take place at the location.
+    #[delete("/locations/<id>")]
+    fn purge_location(store: Store, _admin: Admin, id: Id<Location>) -> Result<Json<Location>> {
+        store.purge_location(id).map_err(ApiError::from).map(Json)
+    }
+
+    pub fn routes() -> Vec<Route> {
+        routes![
+            all,
+            restore_event,
+            purge_event,
+            restore_location,
+            purge_location
+        ]
+    }
+}
+
+mod import {
+    use std::io::Read;
+
+    use super::ApiError;
+    use crate::auth::Admin;
+    use crate::calendar::{self, ImportReport};
+    use crate::store::Store;
+
+    use rocket::{Data, Route};
+    use rocket_contrib::json::Json;
+
+    /// Larger calendar files are rejected.
+    const SIZE_LIMIT: u64 = 1024 * 1024;
+
+    /// Only reports what would be imported, unless the import is confirmed.
+    #[post("/ics?<confirm>", data = "<data>")]
+    fn ics(
+        store: Store,
+        _admin: Admin,
+        confirm: Option<bool>,
+        data: Data,
+    ) -> Result<Json<ImportReport>, ApiError> {
+        // Reading one byte more than allowed tells whether the file is too large.
+        let mut bytes = Vec::new();
+        data.open()
+            .take(SIZE_LIMIT + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|err| ApiError::Unprocessable(err.to_string()))?;
+        if bytes.len() as u64 > SIZE_LIMIT {
+            return Err(ApiError::TooLarge(format!(
+                "Calendar files must not be larger than {} bytes.",
+                SIZE_LIMIT
+            )));
+        }
+        let text = String::from_utf8(bytes)
+            .map_err(|_| ApiError::Unprocessable("The file is not valid UTF-8.".to_string()))?;
+
+        calendar::import(&store, &text, confirm.unwrap_or(false))
+            .map_err(ApiError::from)
+            .map(Json)
+    }
+
+    pub fn routes() -> Vec<Route> {
+        routes![ics]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ops::Deref;
+    use std::path::PathBuf;
+
+    use rocket::config::{Config, Environment, Value};
+    use rocket::http::{ContentType, Status};
+    use rocket::local::Client;
+    use uuid::Uuid;
+
+    use crate::auth;
+    use crate::store::Store;
+
+    const ADMIN_NAME: &str = "admin";
+    const ADMIN_PASSWORD: &str = "correct horse battery staple";
+
+    /// A client for the API backed by its own database file, which is removed afterwards.
+    /// An in-memory database would not do, because every pooled connection opens a separate one,
+    /// and the `Admin` guard takes a second connection.
+    struct TestClient {
+        client: Client,
+        path: PathBuf,
+    }
+
+    impl Deref for TestClient {
+        type Target = Client;
+
+        fn deref(&self) -> &Client {
+            &self.client
+        }
+    }
+
+    impl Drop for TestClient {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn client() -> TestClient {
+        let path =
+            std::env::temp_dir().join(format!("lindyhop-api-test-{}.sqlite", Uuid::new_v4()));
+
+        let mut database = HashMap::new();
+        database.insert("url", Value::from(path.to_string_lossy().into_owned()));
+        database.insert("pool_size", Value::from(2));
+        let mut databases = HashMap::new();
+        databases.insert("sqlite_database", Value::from(database));
+
+        let config = Config::build(Environment::Development)
+            .extra("databases", databases)
+            .extra("admin_name", ADMIN_NAME)
+            .extra("admin_password", ADMIN_PASSWORD)
+            .finalize()
+            .expect("Invalid test config.");
+        let rocket = rocket::custom(config)
+            .attach(Store::fairing())
+            .attach(auth::fairing())
+            .mount("/", auth::routes());
+
+        let client = Client::new(super::mount(rocket, "/api")).expect("Invalid rocket.");
+        TestClient { client, path }
+    }
+
+    fn log_in(client: &Client, password: &str) -> Status {
+        client
+            .post("/login")
+            .header(ContentType::Form)
+            .body(format!("name={}&password={}", ADMIN_NAME, password))
+            .dispatch()
+            .status()
+    }
+
+    fn error(response: &mut rocket::local::LocalResponse) -> serde_json::Value {
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let body: serde_json::Value =
+            serde_json::from_str(&response.body_string().unwrap_or_default())
+                .expect("The body is not JSON.");
+        body["error"].clone()
+    }
+
+    #[test]
+    fn api_errors_are_sent_as_json() {
+        let client = client();
+        let mut response = client.get("/api/unknown").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(error(&mut response), "not_found");
+    }
+
+    #[test]
+    fn page_errors_are_sent_as_html() {
+        let client = client();
+        let response = client.get("/apiary").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+    }
+
+    #[test]
+    fn events_are_only_paged_when_sorted() {
+        let client = client();
+
+        let mut unsorted = client.get("/api/events?limit=10").dispatch();
+        assert_eq!(unsorted.status(), Status::UnprocessableEntity);
+        assert_eq!(error(&mut unsorted), "unprocessable");
+
+        let mut unknown = client.get("/api/events?sort=title&offset=10").dispatch();
+        assert_eq!(unknown.status(), Status::UnprocessableEntity);
+        assert_eq!(error(&mut unknown), "unprocessable");
+
+        let mut sorted = client
+            .get("/api/events?sort=next_occurrence&limit=10")
+            .dispatch();
+        assert_eq!(sorted.status(), Status::Ok);
+        assert_eq!(sorted.body_string().as_deref(), Some("[]"));
+    }
+
+    #[test]
+    fn writes_require_a_session() {
+        let client = client();
+
+        let mut response = client
+            .post("/api/locations")
+            .header(ContentType::JSON)
+            .body(r#"{"name": "Chico Mendes", "address": "Pontstraße 41"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(error(&mut response), "unauthorized");
+
+        assert_eq!(log_in(&client, "wrong"), Status::SeeOther);
+        let mut still_anonymous = client
+            .post("/api/locations")
+            .header(ContentType::JSON)
+            .body(r#"{"name": "Chico Mendes", "address": "Pontstraße 41"}"#)
+            .dispatch();
+        assert_eq!(still_anonymous.status(), Status::Unauthorized);
+        assert_eq!(error(&mut still_anonymous), "unauthorized");
+    }
+
+    #[test]
+    fn admins_write_after_logging_in() {
+        let client = client();
+        assert_eq!(log_in(&client, ADMIN_PASSWORD), Status::SeeOther);
+
+        let mut created = client
+            .post("/api/locations")
+            .header(ContentType::JSON)
+            .body(r#"{"name": "Chico Mendes", "address": "Pontstraße 41"}"#)
+            .dispatch();
+        assert_eq!(created.status(), Status::Ok);
+        let id: String = serde_json::from_str(&created.body_string().unwrap_or_default())
+            .expect("The body is not an id.");
+
+        let mut read = client.get(format!("/api/locations/{}", id)).dispatch();
+        assert_eq!(read.status(), Status::Ok);
+        let location: serde_json::Value =
+            serde_json::from_str(&read.body_string().unwrap_or_default())
+                .expect("The body is not JSON.");
+        assert_eq!(location["name"], "Chico Mendes");
+    }
+
+    #[test]
+    fn write_errors_are_sent_as_json() {
+        let client = client();
+        assert_eq!(log_in(&client, ADMIN_PASSWORD), Status::SeeOther);
+
+        let mut invalid = client
+            .post("/api/locations")
+            .header(ContentType::JSON)
+            .body(r#"{"name": " ", "address": "Pontstraße 41"}"#)
+            .dispatch();
+        assert_eq!(invalid.status(), Status::UnprocessableEntity);
+        let body: serde_json::Value =
+            serde_json::from_str(&invalid.body_string().unwrap_or_default())
+                .expect("The body is not JSON.");
+        assert_eq!(body["error"], "invalid");
+        assert!(body["fields"]["name"].is_array(), "{}", body);
+
+        let mut malformed = client
+            .post("/api/locations")
+            .header(ContentType::JSON)
+            .body(r#"{"name": 42}"#)
+            .dispatch();
+        assert_eq!(malformed.status(), Status::UnprocessableEntity);
+        assert_eq!(error(&mut malformed), "unprocessable");
+
+        let mut missing = client
+            .delete(format!("/api/locations/{}", Uuid::new_v4()))
+            .dispatch();
+        assert_eq!(missing.status(), Status::NotFound);
+        assert_eq!(error(&mut missing), "not_found");
+    }
+
+    #[test]
+    fn invitations_are_only_accepted_with_a_long_enough_password() {
+        use crate::store::{Invitation, User};
+
+        let client = client();
+        let store = Store::get_one(client.rocket()).expect("Database connection failed.");
+        let user = User {
+            name: "Frankie".to_string(),
+        };
+        let id = store
+            .create_user(user, ADMIN_PASSWORD)
+            .expect("Creating the user failed.");
+        let Invitation { token, .. } = store
+            .create_invitation(id)
+            .expect("Creating the invitation failed.");
+        let accept = |password: &str| {
+            client
+                .post("/api/users/invitations")
+                .header(ContentType::JSON)
+                .body(json!({ "token": token, "password": password }).to_string())
+                .dispatch()
+        };
+
+        let mut blank = accept("   ");
+        assert_eq!(blank.status(), Status::UnprocessableEntity);
+        let body: serde_json::Value =
+            serde_json::from_str(&blank.body_string().unwrap_or_default())
+                .expect("The body is not JSON.");
+        assert_eq!(body["error"], "invalid");
+        assert!(body["fields"]["password"].is_array(), "{}", body);
+
+        assert_eq!(accept("swingout").status(), Status::Ok);
+    }
+}
@@ -0,0 +1,195 @@
+use maud::{html, Markup, DOCTYPE};
+use rocket::fairing::AdHoc;
+use rocket::http::{Cookie, Cookies, Status};
+use rocket::request::{self, FlashMessage, Form, FromRequest, Request};
+use rocket::response::{Flash, Redirect};
+use rocket::{Outcome, Route};
+use uuid::Uuid;
+
+use crate::store::{Acceptance, Id, Store, User, Validate, MIN_PASSWORD_LENGTH};
+
+const SESSION_COOKIE: &str = "user_id";
+
+/// Request guard that succeeds only for requests of a logged in admin.
+pub struct Admin(pub Id<User>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let store = match request.guard::<Store>() {
+            Outcome::Success(store) => store,
+            _ => return Outcome::Failure((Status::ServiceUnavailable, ())),
+        };
+
+        match session_user(request) {
+            Some(id) if store.user_exists(id.clone()).unwrap_or(false) => {
+                Outcome::Success(Admin(id))
+            }
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// The user the session cookie belongs to, without checking whether they still exist.
+pub fn session_user(request: &Request) -> Option<Id<User>> {
+    request
+        .cookies()
+        .get_private(SESSION_COOKIE)
+        .and_then(|cookie| Uuid::parse_str(cookie.value()).ok())
+        .map(Id::from)
+}
+
+/// Creates the first admin from the `admin_name` and `admin_password` config values,
+/// if there are no admins yet. All further admins are added by existing ones.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_attach("Initial Admin", |rocket| {
+        let store = Store::get_one(&rocket).expect("Database connection failed.");
+        if store.has_users().expect("Loading from database failed.") {
+            return Ok(rocket);
+        }
+
+        let config = rocket.config();
+        match (config.get_str("admin_name"), config.get_str("admin_password")) {
+            (Ok(name), Ok(password)) => {
+                let user = User {
+                    name: name.to_string(),
+                };
+                store
+                    .create_user(user, password)
+                    .expect("Creating the initial admin failed.");
+            }
+            _ => eprintln!(
+                "There are no admins yet. Set 'admin_name' and 'admin_password' in the config to create one."
+            ),
+        }
+
+        Ok(rocket)
+    })
+}
+
+#[derive(FromForm)]
+struct Credentials {
+    name: String,
+    password: String,
+}
+
+#[get("/login")]
+fn login_page(flash: Option<FlashMessage>) -> Markup {
+    page(html! {
+        form.login action="login" method="post" {
+            @if let Some(message) = flash {
+                p.error { ( message.msg() ) }
+            }
+            label {
+                "Name"
+                input type="text" name="name" required?;
+            }
+            label {
+                "Passwort"
+                input type="password" name="password" required?;
+            }
+            button type="submit" { "Anmelden" }
+        }
+    })
+}
+
+#[post("/login", data = "<credentials>")]
+fn login(
+    store: Store,
+    mut cookies: Cookies,
+    credentials: Form<Credentials>,
+) -> Result<Redirect, Flash<Redirect>> {
+    match store.authenticate(&credentials.name, &credentials.password) {
+        Ok(Some(id)) => {
+            cookies.add_private(Cookie::new(SESSION_COOKIE, id.to_string()));
+            Ok(Redirect::to("/admin"))
+        }
+        _ => Err(Flash::error(
+            Redirect::to("/login"),
+            "Name oder Passwort ist falsch.",
+        )),
+    }
+}
+
+#[get("/invitation?<token>")]
+fn invitation_page(token: String, flash: Option<FlashMessage>) -> Markup {
+    page(html! {
+        form.login action="invitation" method="post" {
+            @if let Some(message) = flash {
+                p.error { ( message.msg() ) }
+            }
+            input type="hidden" name="token" value=( token );
+            label {
+                "Neues Passwort"
+                input type="password" name="password" required? minlength=( MIN_PASSWORD_LENGTH );
+            }
+            button type="submit" { "Passwort setzen" }
+        }
+    })
+}
+
+#[post("/invitation", data = "<acceptance>")]
+fn accept_invitation(
+    store: Store,
+    mut cookies: Cookies,
+    acceptance: Form<Acceptance>,
+) -> Result<Redirect, Flash<Redirect>> {
+    if acceptance.validate(&store).is_err() {
+        return Err(Flash::error(
+            Redirect::to(uri!(invitation_page: &acceptance.token)),
+            format!(
+                "Das Passwort muss mindestens {} Zeichen lang sein.",
+                MIN_PASSWORD_LENGTH
+            ),
+        ));
+    }
+
+    match store.accept_invitation(&acceptance) {
+        Ok(id) => {
+            cookies.add_private(Cookie::new(SESSION_COOKIE, id.to_string()));
+            Ok(Redirect::to("/admin"))
+        }
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "Die Einladung ist ungültig oder abgelaufen.",
+        )),
+    }
+}
+
+#[post("/logout")]
+fn logout(mut cookies: Cookies) -> Redirect {
+    cookies.remove_private(Cookie::named(SESSION_COOKIE));
+    Redirect::to("/")
+}
+
+fn page(content: Markup) -> Markup {
+    html! {
+        ( DOCTYPE )
+        html lang="de" {
+            head {
+                meta name="viewport" content="width=device-width, initial-scale=1";
+
+                link href="static/main.css" rel="stylesheet";
+            }
+            body {
+                header {
+                    h1 { "Lindy Hop Aachen" }
+                }
+                main {
+                    ( content )
+                }
+            }
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        login_page,
+        login,
+        invitation_page,
+        accept_invitation,
+        logout
+    ]
+}
@@ -0,0 +1,878 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use diesel::result::QueryResult;
+use serde::Serialize;
+
+use crate::store::{
+    Actions, Event, EventWithOccurrences, FirstOccurrence, Frequency, Id, Location, Occurrence,
+    OccurrenceFilter, OccurrenceWithLocation, Recurrence, Store, Validate, ValidationError,
+    ValidationErrors,
+};
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    /// Whether the changes were saved. Otherwise, the report only describes what would change.
+    pub committed: bool,
+    pub locations: Vec<Imported<Location>>,
+    pub events: Vec<Imported<EventWithOccurrences, Event>>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// `IdItem` is the type the id refers to, e. g. `Event` for an `EventWithOccurrences`.
+#[derive(Serialize, Debug)]
+pub struct Imported<T, IdItem = T> {
+    pub id: Id<IdItem>,
+    pub action: Action,
+    pub item: T,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Create,
+    /// The item already exists. For events, the new occurrences are added to the existing ones.
+    Merge,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Skipped {
+    pub summary: String,
+    pub reason: String,
+    /// The messages for each invalid field, if the event or its location is invalid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<ValidationErrors>,
+}
+
+/// Imports the events of an iCalendar file, e. g. one exported from Google Calendar.
+/// Events with the same summary are grouped into one event. Nothing is saved unless `commit` is set.
+pub fn import(store: &Store, text: &str, commit: bool) -> QueryResult<ImportReport> {
+    let mut skipped = Vec::new();
+    let mut entries = Vec::new();
+    for properties in components(text, "VEVENT") {
+        match CalendarEvent::from_properties(&properties) {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => skipped.push(Skipped {
+                summary: find_text(&properties, "SUMMARY").unwrap_or_default(),
+                reason,
+                errors: None,
+            }),
+        }
+    }
+
+    // Single instances of a recurring event that were moved or cancelled are listed separately,
+    // with the date they replace as their `RECURRENCE-ID`.
+    let mut replaced_dates: HashMap<String, Vec<NaiveDate>> = HashMap::new();
+    for entry in &entries {
+        if let Some(date) = entry.recurrence_id {
+            replaced_dates
+                .entry(entry.uid.clone())
+                .or_default()
+                .push(date);
+        }
+    }
+
+    let mut groups: Vec<(String, Vec<CalendarEvent>)> = Vec::new();
+    for entry in entries {
+        match groups
+            .iter_mut()
+            .find(|(summary, _)| *summary == entry.summary)
+        {
+            Some((_, group)) => group.push(entry),
+            None => groups.push((entry.summary.clone(), vec![entry])),
+        }
+    }
+
+    store.try_out(commit, || {
+        let mut locations = LocationResolver::new(store);
+        let mut events = Vec::new();
+
+        for (summary, group) in groups {
+            let description = group
+                .iter()
+                .map(|entry| entry.description.clone())
+                .find(|description| !description.is_empty())
+                .unwrap_or_default();
+
+            let mut occurrences = Vec::new();
+            let mut recurrences = Vec::new();
+            for entry in group {
+                if entry.cancelled {
+                    if entry.recurrence_id.is_none() {
+                        skipped.push(Skipped {
+                            summary: summary.clone(),
+                            reason: "The event is cancelled.".to_string(),
+                            errors: None,
+                        });
+                    }
+                    continue;
+                }
+                if entry.location.is_empty() {
+                    skipped.push(Skipped {
+                        summary: summary.clone(),
+                        reason: "The event has no location.".to_string(),
+                        errors: None,
+                    });
+                    continue;
+                }
+                let location_id = match locations.resolve(&entry.location)? {
+                    Ok(location_id) => location_id,
+                    Err(errors) => {
+                        skipped.push(Skipped {
+                            summary: summary.clone(),
+                            reason: "The location is invalid.".to_string(),
+                            errors: Some(errors),
+                        });
+                        continue;
+                    }
+                };
+
+                let first = OccurrenceWithLocation {
+                    id: None,
+                    occurrence: Occurrence {
+                        start: entry.start,
+                        duration: entry.duration(),
+                    },
+                    location_id,
+                    status: None,
+                    overrides: None,
+                    audit: None,
+                };
+
+                let invalid = match entry.rule {
+                    None => {
+                        let invalid = invalid(first.validate(store))?;
+                        if invalid.is_none() {
+                            occurrences.push(first);
+                        }
+                        invalid
+                    }
+                    Some(ref rule) => match parse_rule(rule, &entry.start) {
+                        Ok((frequency, until, count)) => {
+                            let mut exceptions = entry.exceptions.clone();
+                            if let Some(dates) = replaced_dates.get(&entry.uid) {
+                                exceptions.extend(dates);
+                            }
+
+                            let recurrence = Recurrence {
+                                id: None,
+                                first: FirstOccurrence {
+                                    occurrence: first.occurrence,
+                                    location_id: first.location_id,
+                                },
+                                frequency,
+                                until,
+                                count,
+                                exceptions,
+                            };
+                            let invalid = invalid(recurrence.validate(store))?;
+                            if invalid.is_none() {
+                                recurrences.push(recurrence);
+                            }
+                            invalid
+                        }
+                        Err(reason) => {
+                            skipped.push(Skipped {
+                                summary: summary.clone(),
+                                reason,
+                                errors: None,
+                            });
+                            None
+                        }
+                    },
+                };
+                if let Some(errors) = invalid {
+                    skipped.push(Skipped {
+                        summary: summary.clone(),
+                        reason: "The event is invalid.".to_string(),
+                        errors: Some(errors),
+                    });
+                }
+            }
+
+            if occurrences.is_empty() && recurrences.is_empty() {
+                continue;
+            }
+
+            match store.find_event_by_title(&summary)? {
+                Some(id) => {
+                    let existing = store
+                        .read_event_with_occurrences(id.clone(), &OccurrenceFilter::default())?;
+                    let existing_occurrences = existing.occurrences;
+                    let existing_recurrences = existing.recurrences.unwrap_or_default();
+
+                    occurrences.retain(|occurrence| {
+                        !existing_occurrences.iter().any(|existing_occurrence| {
+                            existing_occurrence.occurrence.start == occurrence.occurrence.start
+                                && existing_occurrence.location_id == occurrence.location_id
+                        })
+                    });
+                    recurrences.retain(|recurrence| {
+                        !existing_recurrences
+                            .iter()
+                            .any(|existing_recurrence| existing_recurrence.same_as(recurrence))
+                    });
+
+                    store.add_occurrences(id.clone(), occurrences.clone(), recurrences.clone())?;
+
+                    events.push(Imported {
+                        id,
+                        action: Action::Merge,
+                        item: EventWithOccurrences {
+                            event: existing.event,
+                            occurrences,
+                            recurrences: Some(recurrences),
+                            translations: None,
+                        },
+                    });
+                }
+                None => {
+                    let item = EventWithOccurrences {
+                        event: Event {
+                            title: summary,
+                            teaser: description.lines().next().unwrap_or_default().to_string(),
+                            description,
+                            price: None,
+                            category: None,
+                            tags: None,
+                            publication: None,
+                            audit: None,
+                        },
+                        occurrences,
+                        recurrences: Some(recurrences),
+                        translations: None,
+                    };
+                    if let Some(errors) = invalid(item.validate(store))? {
+                        skipped.push(Skipped {
+                            summary: item.event.title,
+                            reason: "The event is invalid.".to_string(),
+                            errors: Some(errors),
+                        });
+                        continue;
+                    }
+                    let id = store.create_event_with_occurrences(item.clone())?;
+
+                    events.push(Imported {
+                        id,
+                        action: Action::Create,
+                        item,
+                    });
+                }
+            }
+        }
+
+        Ok(ImportReport {
+            committed: commit,
+            locations: locations.imported,
+            events,
+            skipped,
+        })
+    })
+}
+
+/// Finds the location for a `LOCATION` text, creating a new one if none matches.
+struct LocationResolver<'a> {
+    store: &'a Store,
+    existing: HashMap<Id<Location>, Location>,
+    resolved: HashMap<String, Id<Location>>,
+    imported: Vec<Imported<Location>>,
+}
+
+impl<'a> LocationResolver<'a> {
+    fn new(store: &'a Store) -> Self {
+        LocationResolver {
+            store,
+            existing: store.all(),
+            resolved: HashMap::new(),
+            imported: Vec::new(),
+        }
+    }
+
+    /// Fails with the validation errors if a new location would be invalid.
+    fn resolve(&mut self, text: &str) -> QueryResult<Result<Id<Location>, ValidationErrors>> {
+        if let Some(id) = self.resolved.get(text) {
+            return Ok(Ok(id.clone()));
+        }
+
+        let (id, location, action) = match self
+            .existing
+            .iter()
+            .find(|(_, location)| location_matches(location, text))
+        {
+            Some((id, location)) => (id.clone(), location.clone(), Action::Merge),
+            None => {
+                // Calendars usually contain the location as "Name, Street, City".
+                let mut parts = text.splitn(2, ',');
+                let location = Location {
+                    name: parts.next().unwrap_or_default().trim().to_string(),
+                    address: parts.next().unwrap_or_default().trim().to_string(),
+                    audit: None,
+                };
+                if let Some(errors) = invalid(location.validate(self.store))? {
+                    return Ok(Err(errors));
+                }
+                let id = self.store.create(location.clone())?;
+                (id, location, Action::Create)
+            }
+        };
+
+        self.resolved.insert(text.to_string(), id.clone());
+        self.imported.push(Imported {
+            id: id.clone(),
+            action,
+            item: location,
+        });
+
+        Ok(Ok(id))
+    }
+}
+
+/// Tells invalid items apart from failed queries.
+fn invalid(validated: Result<(), ValidationError>) -> QueryResult<Option<ValidationErrors>> {
+    match validated {
+        Ok(()) => Ok(None),
+        Err(ValidationError::Invalid(errors)) => Ok(Some(errors)),
+        Err(ValidationError::Database(err)) => Err(err),
+    }
+}
+
+/// Whether a `LOCATION` text is the location's name or its full address, optionally preceded
+/// by a name, e. g. "Chico Mendes, Pontstraße 41, 52062 Aachen". Parts of an address do not
+/// match, since "Pontstraße 4" is a different place than "Pontstraße 41".
+fn location_matches(location: &Location, text: &str) -> bool {
+    let text = normalize(text);
+    let name = normalize(&location.name);
+    let address = normalize(&location.address);
+
+    text == name
+        || text.starts_with(&format!("{}, ", name))
+        || (!address.is_empty() && (text == address || text.ends_with(&format!(", {}", address))))
+}
+
+/// Lowercases the text and unifies the whitespace around and within its comma-separated parts.
+fn normalize(text: &str) -> String {
+    text.split(',')
+        .map(|part| part.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(", ")
+        .to_lowercase()
+}
+
+struct CalendarEvent {
+    uid: String,
+    summary: String,
+    description: String,
+    location: String,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    rule: Option<String>,
+    exceptions: Vec<NaiveDate>,
+    recurrence_id: Option<NaiveDate>,
+    cancelled: bool,
+}
+
+impl CalendarEvent {
+    fn from_properties(properties: &[Property]) -> Result<CalendarEvent, String> {
+        let find = |name: &str| properties.iter().find(|property| property.name == name);
+
+        let start_property = find("DTSTART").ok_or("The event has no start.")?;
+        let start = parse_datetime(start_property).ok_or("The start is invalid.")?;
+        let end = match (find("DTEND"), find("DURATION")) {
+            (Some(end), _) => parse_datetime(end).ok_or("The end is invalid.")?,
+            (None, Some(duration)) => {
+                start + parse_duration(&duration.value).ok_or("The duration is invalid.")?
+            }
+            // Events without an end last a whole day if they are all-day events.
+            (None, None) if is_date(start_property) => start + chrono::Duration::days(1),
+            (None, None) => start,
+        };
+
+        let exceptions = properties
+            .iter()
+            .filter(|property| property.name == "EXDATE")
+            .flat_map(|property| {
+                property
+                    .value
+                    .split(',')
+                    .filter_map(|value| parse_datetime_value(value, is_date(property)))
+                    .map(|datetime| datetime.date())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Ok(CalendarEvent {
+            uid: find_text(properties, "UID").unwrap_or_default(),
+            summary: find_text(properties, "SUMMARY").unwrap_or_default(),
+            description: find_text(properties, "DESCRIPTION").unwrap_or_default(),
+            location: find_text(properties, "LOCATION").unwrap_or_default(),
+            start,
+            end,
+            rule: find("RRULE").map(|property| property.value.clone()),
+            exceptions,
+            recurrence_id: find("RECURRENCE-ID")
+                .and_then(parse_datetime)
+                .map(|datetime| datetime.date()),
+            cancelled: find("STATUS").map_or(false, |property| property.value == "CANCELLED"),
+        })
+    }
+
+    fn duration(&self) -> u32 {
+        (self.end - self.start).num_minutes().max(0) as u32
+    }
+}
+
+/// Translates the rules this site can represent, i. e. weekly, biweekly, and on the nth weekday of each month.
+fn parse_rule(
+    rule: &str,
+    start: &NaiveDateTime,
+) -> Result<(Frequency, Option<NaiveDate>, Option<u32>), String> {
+    let unsupported = || format!("The recurrence rule '{}' is not supported.", rule);
+
+    let parts: HashMap<String, String> = rule
+        .split(';')
+        .filter_map(|part| {
+            let mut key_value = part.splitn(2, '=');
+            Some((
+                key_value.next()?.to_uppercase(),
+                key_value.next()?.to_uppercase(),
+            ))
+        })
+        .collect();
+    let known_parts = ["FREQ", "INTERVAL", "BYDAY", "UNTIL", "COUNT", "WKST"];
+    if parts.keys().any(|key| !known_parts.contains(&key.as_str())) {
+        return Err(unsupported());
+    }
+
+    let interval = match parts.get("INTERVAL") {
+        Some(interval) => interval.parse::<u32>().map_err(|_| unsupported())?,
+        None => 1,
+    };
+    let weekday = weekday_code(start.weekday());
+    let by_day = parts.get("BYDAY").map(String::as_str);
+
+    let frequency = match (parts.get("FREQ").map(String::as_str), interval) {
+        (Some("WEEKLY"), 1) | (Some("WEEKLY"), 2) => {
+            if by_day.map_or(false, |day| day != weekday) {
+                return Err(unsupported());
+            }
+            if interval == 1 {
+                Frequency::Weekly
+            } else {
+                Frequency::Biweekly
+            }
+        }
+        (Some("MONTHLY"), 1) => {
+            let by_day = by_day.ok_or_else(unsupported)?;
+            if !by_day.ends_with(weekday) {
+                return Err(unsupported());
+            }
+            let nth = by_day[..by_day.len() - weekday.len()]
+                .parse::<i8>()
+                .map_err(|_| unsupported())?;
+            Frequency::MonthlyNthWeekday { nth }
+        }
+        _ => return Err(unsupported()),
+    };
+
+    let until = match parts.get("UNTIL") {
+        Some(until) => Some(
+            parse_datetime_value(until, until.len() == 8)
+                .ok_or_else(unsupported)?
+                .date(),
+        ),
+        None => None,
+    };
+    let count = match parts.get("COUNT") {
+        Some(count) => Some(count.parse::<u32>().map_err(|_| unsupported())?),
+        None => None,
+    };
+
+    Ok((frequency, until, count))
+}
+
+fn weekday_code(weekday: chrono::Weekday) -> &'static str {
+    use chrono::Weekday::*;
+
+    match weekday {
+        Mon => "MO",
+        Tue => "TU",
+        Wed => "WE",
+        Thu => "TH",
+        Fri => "FR",
+        Sat => "SA",
+        Sun => "SU",
+    }
+}
+
+struct Property {
+    name: String,
+    parameters: HashMap<String, String>,
+    value: String,
+}
+
+/// Lists the properties of all components called `name`, leaving out nested components like alarms.
+fn components(text: &str, name: &str) -> Vec<Vec<Property>> {
+    let mut components = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    let mut nesting = 0;
+
+    for property in properties(text) {
+        let is_begin = property.name == "BEGIN";
+        let is_end = property.name == "END";
+
+        if current.is_none() {
+            if is_begin && property.value == name {
+                current = Some(Vec::new());
+            }
+        } else if is_begin {
+            nesting += 1;
+        } else if is_end && nesting > 0 {
+            nesting -= 1;
+        } else if is_end {
+            components.extend(current.take());
+        } else if nesting == 0 {
+            if let Some(component) = current.as_mut() {
+                component.push(property);
+            }
+        }
+    }
+
+    components
+}
+
+fn properties(text: &str) -> Vec<Property> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        // Long lines are folded into several lines, where each continuation starts with whitespace.
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(previous) = lines.last_mut() {
+                previous.push_str(&line[1..]);
+            }
+        } else if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+
+    lines
+        .iter()
+        .filter_map(|line| parse_property(line))
+        .collect()
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let (separator, _) = line.char_indices().find(|&(_, character)| {
+        if character == '"' {
+            in_quotes = !in_quotes;
+        }
+        character == ':' && !in_quotes
+    })?;
+
+    let mut head = line[..separator].split(';');
+    let name = head.next()?.to_uppercase();
+    let parameters = head
+        .filter_map(|parameter| {
+            let mut key_value = parameter.splitn(2, '=');
+            Some((
+                key_value.next()?.to_uppercase(),
+                key_value.next()?.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+
+    Some(Property {
+        name,
+        parameters,
+        value: line[separator + 1..].to_string(),
+    })
+}
+
+fn find_text(properties: &[Property], name: &str) -> Option<String> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| unescape(&property.value))
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut characters = text.chars();
+
+    while let Some(character) = characters.next() {
+        if character == '\\' {
+            match characters.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            }
+        } else {
+            unescaped.push(character);
+        }
+    }
+
+    unescaped.trim().to_string()
+}
+
+fn is_date(property: &Property) -> bool {
+    property
+        .parameters
+        .get("VALUE")
+        .map_or(false, |value| value == "DATE")
+}
+
+fn parse_datetime(property: &Property) -> Option<NaiveDateTime> {
+    parse_datetime_value(&property.value, is_date(property))
+}
+
+/// Parses dates, local times and UTC times. Times with a `TZID` are assumed to be local times in Aachen.
+fn parse_datetime_value(value: &str, is_date: bool) -> Option<NaiveDateTime> {
+    let value = value.trim();
+
+    if is_date {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(|date| date.and_hms(0, 0, 0))
+    } else if value.ends_with('Z') {
+        NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+            .ok()
+            .map(super::utc_to_local)
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+    }
+}
+
+/// Parses durations like `PT1H30M` or `P1D`.
+fn parse_duration(value: &str) -> Option<chrono::Duration> {
+    let mut duration = chrono::Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for character in value.trim().trim_start_matches('+').chars() {
+        match character {
+            'P' => {}
+            'T' => in_time = true,
+            '0'..='9' => number.push(character),
+            unit => {
+                let amount = number.parse::<i64>().ok()?;
+                number.clear();
+                duration = duration
+                    + match (unit, in_time) {
+                        ('W', false) => chrono::Duration::weeks(amount),
+                        ('D', false) => chrono::Duration::days(amount),
+                        ('H', true) => chrono::Duration::hours(amount),
+                        ('M', true) => chrono::Duration::minutes(amount),
+                        ('S', true) => chrono::Duration::seconds(amount),
+                        _ => return None,
+                    };
+            }
+        }
+    }
+
+    Some(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monday() -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, 7, 1).and_hms(20, 0, 0)
+    }
+
+    #[test]
+    fn parses_weekly_rules() {
+        assert_eq!(
+            parse_rule("FREQ=WEEKLY", &monday()),
+            Ok((Frequency::Weekly, None, None))
+        );
+        assert_eq!(
+            parse_rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO;WKST=MO", &monday()),
+            Ok((Frequency::Biweekly, None, None))
+        );
+        assert_eq!(
+            parse_rule("freq=weekly;count=3", &monday()),
+            Ok((Frequency::Weekly, None, Some(3)))
+        );
+    }
+
+    #[test]
+    fn parses_monthly_rules() {
+        assert_eq!(
+            parse_rule("FREQ=MONTHLY;BYDAY=1MO", &monday()),
+            Ok((Frequency::MonthlyNthWeekday { nth: 1 }, None, None))
+        );
+        assert_eq!(
+            parse_rule("FREQ=MONTHLY;BYDAY=-1MO", &monday()),
+            Ok((Frequency::MonthlyNthWeekday { nth: -1 }, None, None))
+        );
+    }
+
+    #[test]
+    fn parses_until_as_date_or_utc_time() {
+        let until = Some(NaiveDate::from_ymd(2019, 7, 29));
+
+        assert_eq!(
+            parse_rule("FREQ=WEEKLY;UNTIL=20190729", &monday()),
+            Ok((Frequency::Weekly, until, None))
+        );
+        assert_eq!(
+            parse_rule("FREQ=WEEKLY;UNTIL=20190729T215959Z", &monday()),
+            Ok((Frequency::Weekly, until, None))
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        for rule in &[
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=3",
+            "FREQ=WEEKLY;BYDAY=TU",
+            "FREQ=WEEKLY;BYDAY=MO,TH",
+            "FREQ=WEEKLY;BYMONTH=7",
+            "FREQ=MONTHLY",
+            "FREQ=MONTHLY;BYDAY=1TU",
+            "FREQ=MONTHLY;BYMONTHDAY=1",
+            "FREQ=WEEKLY;COUNT=many",
+        ] {
+            assert!(
+                parse_rule(rule, &monday()).is_err(),
+                "{} was accepted",
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(
+            parse_duration("PT1H30M"),
+            Some(chrono::Duration::minutes(90))
+        );
+        assert_eq!(parse_duration("P1DT2H"), Some(chrono::Duration::hours(26)));
+        assert_eq!(parse_duration("P1W"), Some(chrono::Duration::weeks(1)));
+        assert_eq!(
+            parse_duration("+PT15M"),
+            Some(chrono::Duration::minutes(15))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration("P1H"), None);
+        assert_eq!(parse_duration("PT1D"), None);
+        assert_eq!(parse_duration("PTH"), None);
+        assert_eq!(parse_duration("PT1X"), None);
+    }
+
+    #[test]
+    fn parses_names_parameters_and_values() {
+        let property = parse_property("dtstart;TZID=Europe/Berlin:20190701T200000")
+            .expect("The property was not parsed.");
+
+        assert_eq!(property.name, "DTSTART");
+        assert_eq!(
+            property.parameters.get("TZID").map(String::as_str),
+            Some("Europe/Berlin")
+        );
+        assert_eq!(property.value, "20190701T200000");
+    }
+
+    #[test]
+    fn ignores_colons_in_quoted_parameters() {
+        let property = parse_property(r#"LOCATION;ALTREP="https://example.com":Chico Mendes"#)
+            .expect("The property was not parsed.");
+
+        assert_eq!(
+            property.parameters.get("ALTREP").map(String::as_str),
+            Some("https://example.com")
+        );
+        assert_eq!(property.value, "Chico Mendes");
+    }
+
+    #[test]
+    fn rejects_lines_without_value() {
+        assert!(parse_property("SUMMARY").is_none());
+    }
+
+    #[test]
+    fn lists_components_without_nested_ones() {
+        let text = "BEGIN:VCALENDAR\r\n\
+                    VERSION:2.0\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Lindy Hop Social\r\n\
+                    DESCRIPTION:Tanzen bis \r\n \
+                    spät\r\n\
+                    BEGIN:VALARM\r\n\
+                    ACTION:DISPLAY\r\n\
+                    END:VALARM\r\n\
+                    LOCATION:Chico Mendes\r\n\
+                    END:VEVENT\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Balboa Social\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n";
+
+        let events = components(text, "VEVENT");
+        let names = |properties: &[Property]| {
+            properties
+                .iter()
+                .map(|property| property.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            names(&events[0]),
+            vec!["SUMMARY", "DESCRIPTION", "LOCATION"]
+        );
+        assert_eq!(
+            find_text(&events[0], "DESCRIPTION").as_deref(),
+            Some("Tanzen bis spät")
+        );
+        assert_eq!(names(&events[1]), vec!["SUMMARY"]);
+    }
+
+    #[test]
+    fn unescapes_text() {
+        assert_eq!(
+            unescape(r"Tanzen\, Musik\; mehr\nZeile\NEnde\\ "),
+            "Tanzen, Musik; mehr\nZeile\nEnde\\"
+        );
+        assert_eq!(unescape(r"Ende\"), "Ende");
+    }
+
+    #[test]
+    fn matches_locations_by_name_or_full_address() {
+        let location = Location {
+            name: "Chico Mendes".to_string(),
+            address: "Pontstraße 41, 52062 Aachen".to_string(),
+            audit: None,
+        };
+
+        assert!(location_matches(&location, "chico mendes"));
+        assert!(location_matches(&location, "Chico Mendes, Pontstraße 4"));
+        assert!(location_matches(&location, "Pontstraße  41,52062 Aachen"));
+        assert!(location_matches(
+            &location,
+            "Welthaus, Pontstraße 41, 52062 Aachen"
+        ));
+        assert!(!location_matches(&location, "Pontstraße 4, 52062 Aachen"));
+        assert!(!location_matches(&location, "Chico Mendes Café"));
+    }
+
+    #[test]
+    fn only_matches_addresses_as_a_whole() {
+        let location = Location {
+            name: "Chico Mendes".to_string(),
+            address: "Pontstraße 4".to_string(),
+            audit: None,
+        };
+
+        assert!(location_matches(&location, "Pontstraße 4"));
+        assert!(!location_matches(&location, "Pontstraße 41"));
+        assert!(!location_matches(
+            &location,
+            "Welthaus, Pontstraße 41, 52062 Aachen"
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+//! Renders occurrences as an iCalendar feed and imports events from iCalendar files as described in RFC 5545.
+
+mod import;
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Utc};
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+
+use crate::i18n;
+use crate::store::{Id, Locale, Location, OccurrenceState, OccurrenceWithEvent};
+
+pub use import::{import, ImportReport};
+
+/// All times are stored as local times in Aachen.
+const TIMEZONE: &str = "Europe/Berlin";
+
+const TIMEZONE_DEFINITION: &[&str] = &[
+    "BEGIN:VTIMEZONE",
+    "TZID:Europe/Berlin",
+    "BEGIN:DAYLIGHT",
+    "TZOFFSETFROM:+0100",
+    "TZOFFSETTO:+0200",
+    "TZNAME:CEST",
+    "DTSTART:19700329T020000",
+    "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU",
+    "END:DAYLIGHT",
+    "BEGIN:STANDARD",
+    "TZOFFSETFROM:+0200",
+    "TZOFFSETTO:+0100",
+    "TZNAME:CET",
+    "DTSTART:19701025T030000",
+    "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU",
+    "END:STANDARD",
+    "END:VTIMEZONE",
+];
+
+pub fn render(
+    entries: &[OccurrenceWithEvent],
+    locations: &HashMap<Id<Location>, Location>,
+) -> Content<String> {
+    let timestamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Lindy Hop Aachen//Termine//DE".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Lindy Hop Aachen".to_string(),
+    ];
+    lines.extend(TIMEZONE_DEFINITION.iter().map(|line| line.to_string()));
+
+    for entry in entries {
+        let occurrence = &entry.occurrence.occurrence;
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@lindyhop-aachen", entry.id));
+        lines.push(format!("DTSTAMP:{}", timestamp));
+        lines.push(format!(
+            "DTSTART;TZID={}:{}",
+            TIMEZONE,
+            format_datetime(&occurrence.start)
+        ));
+        lines.push(format!(
+            "DTEND;TZID={}:{}",
+            TIMEZONE,
+            format_datetime(&occurrence.end())
+        ));
+        let event = entry.occurrence.apply_overrides(&entry.event);
+        let note = entry.occurrence.current_overrides().note;
+        let teaser = if note.trim().is_empty() {
+            event.teaser
+        } else {
+            format!("{}\n\n{}", event.teaser, note.trim())
+        };
+        let status = entry.occurrence.current_status();
+        let (summary, description) = match status.state {
+            OccurrenceState::Scheduled => (event.title, teaser),
+            state => {
+                let label = i18n::state_name(Locale::De, state);
+                let notice = if status.note.trim().is_empty() {
+                    label.to_string()
+                } else {
+                    format!("{}: {}", label, status.note.trim())
+                };
+
+                (
+                    format!("[{}] {}", label, event.title),
+                    format!("{}\n\n{}", notice, teaser),
+                )
+            }
+        };
+        lines.push(format!("STATUS:{}", calendar_status(status.state)));
+        lines.push(format!("SUMMARY:{}", escape(&summary)));
+        lines.push(format!("DESCRIPTION:{}", escape(&description)));
+        if let Some(location) = locations.get(&entry.occurrence.location_id) {
+            let text = format!("{}, {}", location.name, location.address);
+            lines.push(format!("LOCATION:{}", escape(&text)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let body = lines.iter().map(|line| fold(line)).collect();
+    Content(ContentType::Calendar, body)
+}
+
+/// A moved occurrence does not take place at its original time, so calendars show it as cancelled.
+fn calendar_status(state: OccurrenceState) -> &'static str {
+    match state {
+        OccurrenceState::Scheduled => "CONFIRMED",
+        OccurrenceState::Tentative => "TENTATIVE",
+        OccurrenceState::Cancelled | OccurrenceState::Moved => "CANCELLED",
+    }
+}
+
+/// Converts a time in UTC to the local time in Aachen, taking daylight saving time into account.
+fn utc_to_local(utc: NaiveDateTime) -> NaiveDateTime {
+    let last_sunday = |month: u32| {
+        let mut day = NaiveDate::from_ymd(utc.year(), month + 1, 1).pred();
+        while day.weekday() != chrono::Weekday::Sun {
+            day = day.pred();
+        }
+        day
+    };
+    let summer_start = last_sunday(3).and_hms(1, 0, 0);
+    let summer_end = last_sunday(10).and_hms(1, 0, 0);
+
+    let offset = if utc >= summer_start && utc < summer_end {
+        2
+    } else {
+        1
+    };
+    utc + chrono::Duration::hours(offset)
+}
+
+fn format_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Terminates the line, splitting it into several lines of at most 75 bytes.
+/// Continuation lines start with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for character in line.chars() {
+        if length + character.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(character);
+        length += character.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, month, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(
+            escape("Tanzen, Musik; mehr\\\r\nZeile\nEnde"),
+            r"Tanzen\, Musik\; mehr\\\nZeile\nEnde"
+        );
+    }
+
+    #[test]
+    fn does_not_fold_short_lines() {
+        assert_eq!(
+            fold("SUMMARY:Lindy Hop Social"),
+            "SUMMARY:Lindy Hop Social\r\n"
+        );
+    }
+
+    #[test]
+    fn folds_lines_after_75_octets() {
+        let line = format!("DESCRIPTION:{}", "a".repeat(100));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(format!("{}{}", lines[0], &lines[1][1..]), line);
+    }
+
+    #[test]
+    fn does_not_split_multi_byte_characters() {
+        // "ä" takes two octets, so the 75th octet would be in the middle of one.
+        let line = format!("SUMMARY:{}", "ä".repeat(50));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert_eq!(lines[0].len(), 74);
+        let unfolded: String = lines[1..].iter().map(|line| &line[1..]).collect();
+        assert_eq!(format!("{}{}", lines[0], unfolded), line);
+    }
+
+    #[test]
+    fn converts_utc_to_standard_time() {
+        assert_eq!(utc_to_local(datetime(1, 15, 12, 0)), datetime(1, 15, 13, 0));
+        assert_eq!(
+            utc_to_local(datetime(12, 31, 23, 30)).date(),
+            NaiveDate::from_ymd(2020, 1, 1)
+        );
+    }
+
+    #[test]
+    fn converts_utc_to_daylight_saving_time() {
+        assert_eq!(utc_to_local(datetime(7, 1, 18, 0)), datetime(7, 1, 20, 0));
+    }
+
+    #[test]
+    fn switches_to_daylight_saving_time_on_the_last_sunday_of_march() {
+        assert_eq!(utc_to_local(datetime(3, 31, 0, 59)), datetime(3, 31, 1, 59));
+        assert_eq!(utc_to_local(datetime(3, 31, 1, 0)), datetime(3, 31, 3, 0));
+    }
+
+    #[test]
+    fn switches_to_standard_time_on_the_last_sunday_of_october() {
+        assert_eq!(
+            utc_to_local(datetime(10, 27, 0, 59)),
+            datetime(10, 27, 2, 59)
+        );
+        assert_eq!(utc_to_local(datetime(10, 27, 1, 0)), datetime(10, 27, 2, 0));
+    }
+}
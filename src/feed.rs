@@ -0,0 +1,256 @@
+//! Renders upcoming occurrences and newly created events as an Atom feed as described in RFC 4287.
+
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+
+use crate::i18n;
+use crate::markdown;
+use crate::store::{Event, Id, Locale, Location, OccurrenceState, OccurrenceWithEvent};
+
+/// The feed is written in the default language, like the calendar.
+const LOCALE: Locale = Locale::De;
+
+/// An event created since then is announced as new.
+pub const NEW_EVENT_DAYS: i64 = 30;
+
+/// Occurrences are listed until then.
+pub const UPCOMING_DAYS: i64 = 28;
+
+pub struct Feed<'a> {
+    /// The address of the website without a trailing slash, e. g. `https://example.org`.
+    pub site_url: &'a str,
+    pub new_events: Vec<(Id<Event>, Event)>,
+    pub entries: Vec<OccurrenceWithEvent>,
+    pub locations: HashMap<Id<Location>, Location>,
+}
+
+struct Entry {
+    id: String,
+    title: String,
+    link: String,
+    updated: NaiveDateTime,
+    published: Option<NaiveDateTime>,
+    /// HTML
+    summary: String,
+}
+
+pub fn render(feed: Feed) -> Content<String> {
+    let mut entries: Vec<Entry> = feed
+        .new_events
+        .iter()
+        .filter_map(|(event_id, event)| {
+            let audit = event.audit.as_ref()?;
+
+            Some(Entry {
+                id: format!("urn:uuid:{}", event_id),
+                title: format!("Neu: {}", event.title),
+                link: format!("{}/events/{}", feed.site_url, event_id),
+                updated: audit.published_at.map_or(audit.updated_at, |published_at| {
+                    published_at.max(audit.updated_at)
+                }),
+                published: audit.published_at,
+                summary: markdown::render(&event.teaser),
+            })
+        })
+        .collect();
+    entries.extend(
+        feed.entries
+            .iter()
+            .map(|entry| occurrence_entry(&feed, entry)),
+    );
+
+    let updated = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or_else(|| Local::now().naive_local());
+
+    let mut lines: Vec<String> = vec![
+        r#"<?xml version="1.0" encoding="utf-8"?>"#.to_string(),
+        format!(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="{}">"#,
+            LOCALE.code()
+        ),
+        "<title>Lindy Hop Aachen</title>".to_string(),
+        format!("<id>{}/feed.atom</id>", escape(feed.site_url)),
+        format!(
+            r#"<link rel="self" href="{}/feed.atom"/>"#,
+            escape(feed.site_url)
+        ),
+        format!(r#"<link href="{}/"/>"#, escape(feed.site_url)),
+        format!("<updated>{}</updated>", format_datetime(&updated)),
+        "<author><name>Lindy Hop Aachen</name></author>".to_string(),
+    ];
+    for entry in entries {
+        lines.push("<entry>".to_string());
+        lines.push(format!("<id>{}</id>", escape(&entry.id)));
+        lines.push(format!("<title>{}</title>", escape(&entry.title)));
+        lines.push(format!(r#"<link href="{}"/>"#, escape(&entry.link)));
+        lines.push(format!(
+            "<updated>{}</updated>",
+            format_datetime(&entry.updated)
+        ));
+        if let Some(published) = entry.published {
+            lines.push(format!(
+                "<published>{}</published>",
+                format_datetime(&published)
+            ));
+        }
+        lines.push(format!(
+            r#"<summary type="html">{}</summary>"#,
+            escape(&entry.summary)
+        ));
+        lines.push("</entry>".to_string());
+    }
+    lines.push("</feed>".to_string());
+
+    Content(
+        ContentType::new("application", "atom+xml"),
+        lines.join("\n"),
+    )
+}
+
+fn occurrence_entry(feed: &Feed, entry: &OccurrenceWithEvent) -> Entry {
+    let event = entry.occurrence.apply_overrides(&entry.event);
+    let start = entry.occurrence.occurrence.start;
+    let status = entry.occurrence.current_status();
+
+    let mut title = format!(
+        "{} {}: {}",
+        i18n::format_date(LOCALE, &start.date()),
+        start.format("%H:%M"),
+        event.title
+    );
+    let mut summary = String::new();
+    if status.state != OccurrenceState::Scheduled {
+        let label = i18n::state_name(LOCALE, status.state);
+        title = format!("[{}] {}", label, title);
+        summary.push_str(&markdown::render(&if status.note.trim().is_empty() {
+            label.to_string()
+        } else {
+            format!("**{}:** {}", label, status.note.trim())
+        }));
+    }
+    // The location is plain text, unlike the teaser.
+    if let Some(location) = feed.locations.get(&entry.occurrence.location_id) {
+        summary.push_str(&format!(
+            "<p>{}</p>",
+            escape(&format!("{}, {}", location.name, location.address))
+        ));
+    }
+    summary.push_str(&markdown::render(&event.teaser));
+
+    // Changes to the event, e. g. a new teaser, also change its occurrences. Occurrences generated
+    // by a recurrence rule only change with their event.
+    let updated = entry
+        .occurrence
+        .audit
+        .iter()
+        .chain(&entry.event.audit)
+        .map(|audit| audit.updated_at)
+        .max()
+        .unwrap_or(start);
+
+    Entry {
+        id: format!("urn:uuid:{}", entry.id),
+        title,
+        link: format!("{}/events/{}", feed.site_url, entry.event_id),
+        updated,
+        published: None,
+        summary,
+    }
+}
+
+/// Formats a local time as an RFC 3339 timestamp, e. g. `2019-08-31T20:00:00+02:00`.
+fn format_datetime(datetime: &NaiveDateTime) -> String {
+    match Local.from_local_datetime(datetime).earliest() {
+        Some(local) => local.to_rfc3339(),
+        None => datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::store::{Audit, Occurrence, OccurrenceWithLocation};
+
+    fn event(title: &str, teaser: &str) -> Event {
+        let created_at = NaiveDate::from_ymd(2019, 8, 31).and_hms(12, 0, 0);
+
+        Event {
+            title: title.to_string(),
+            teaser: teaser.to_string(),
+            description: String::new(),
+            price: None,
+            category: None,
+            tags: None,
+            publication: None,
+            audit: Some(Audit {
+                created_at,
+                updated_at: created_at,
+                updated_by: None,
+                published_at: Some(created_at),
+            }),
+        }
+    }
+
+    #[test]
+    fn escapes_titles_links_and_summaries() {
+        let location_id: Id<Location> = Uuid::new_v4().into();
+        let mut locations = HashMap::new();
+        locations.insert(
+            location_id.clone(),
+            Location {
+                name: "Chico <Mendes>".to_string(),
+                address: "Pontstraße 41".to_string(),
+                audit: None,
+            },
+        );
+        let feed = Feed {
+            site_url: "https://example.org/?a=1&b=\"2\"",
+            new_events: vec![(
+                Uuid::new_v4().into(),
+                event("Swing & <Blues>", "**Tanzen**"),
+            )],
+            entries: vec![OccurrenceWithEvent {
+                id: Uuid::new_v4().into(),
+                occurrence: OccurrenceWithLocation {
+                    id: None,
+                    occurrence: Occurrence {
+                        start: NaiveDate::from_ymd(2019, 9, 6).and_hms(20, 0, 0),
+                        duration: 120,
+                    },
+                    location_id,
+                    status: None,
+                    overrides: None,
+                    audit: None,
+                },
+                event_id: Uuid::new_v4().into(),
+                event: event("Lindy Hop Social", "Tanzen"),
+            }],
+            locations,
+        };
+
+        let body = render(feed).1;
+
+        assert!(body.contains("<title>Neu: Swing &amp; &lt;Blues&gt;</title>"));
+        assert!(body.contains("&lt;strong&gt;Tanzen&lt;/strong&gt;"));
+        assert!(body.contains("Chico &amp;lt;Mendes&amp;gt;"));
+        assert!(body.contains(r#"<link href="https://example.org/?a=1&amp;b=&quot;2&quot;/"/>"#));
+        assert!(!body.contains("<Blues>"));
+        assert!(!body.contains("<Mendes>"));
+    }
+}
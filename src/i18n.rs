@@ -0,0 +1,324 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use rocket::http::Cookie;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+
+use crate::store::{Category, Frequency, Locale, OccurrenceState, Price, PriceKind};
+
+const LOCALE_COOKIE: &str = "locale";
+
+/// Picks the language of the website. A language prefix in the path, e. g. `/en/events`, is
+/// remembered in a cookie. Otherwise, the cookie or the browser's `Accept-Language` decide.
+impl<'a, 'r> FromRequest<'a, 'r> for Locale {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let from_path = request.uri().segments().next().and_then(Locale::from_code);
+        if let Some(locale) = from_path {
+            request.cookies().add(
+                Cookie::build(LOCALE_COOKIE, locale.code())
+                    .path("/")
+                    .finish(),
+            );
+            return Outcome::Success(locale);
+        }
+
+        let from_cookie = request
+            .cookies()
+            .get(LOCALE_COOKIE)
+            .and_then(|cookie| Locale::from_code(cookie.value()));
+        let from_header = request
+            .headers()
+            .get_one("Accept-Language")
+            .and_then(preferred_locale);
+
+        Outcome::Success(from_cookie.or(from_header).unwrap_or_default())
+    }
+}
+
+/// Picks the supported language with the highest quality from an `Accept-Language` header,
+/// e. g. `en-US,en;q=0.9,de;q=0.8`.
+fn preferred_locale(header: &str) -> Option<Locale> {
+    let mut candidates: Vec<(Locale, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let language = tag.split('-').next()?;
+            let quality = parts
+                .filter_map(|part| part.trim().trim_start_matches("q=").parse().ok())
+                .next()
+                .unwrap_or(1.0);
+
+            Locale::from_code(language).map(|locale| (locale, quality))
+        })
+        .collect();
+    // The sort is stable, so the header's order decides between equal qualities.
+    candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    candidates.first().map(|(locale, _)| *locale)
+}
+
+/// An internal link that keeps the language, e. g. `/en/events` for `/events` or
+/// `/en?tag=balboa` for `/?tag=balboa`.
+pub fn link(locale: Locale, path: &str) -> String {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() || path.starts_with('?') {
+        format!("/{}{}", locale.code(), path)
+    } else {
+        format!("/{}/{}", locale.code(), path)
+    }
+}
+
+/// The texts of the website.
+#[derive(Clone, Copy)]
+pub enum Message {
+    AllEvents,
+    NextDate,
+    UpcomingDates,
+    NoUpcomingDates,
+    NoUpcomingDatesHere,
+    ToBeAnnounced,
+    Once,
+    RemoveFilter,
+    Preview,
+}
+
+pub fn text(locale: Locale, message: Message) -> &'static str {
+    use Message::*;
+
+    match (locale, message) {
+        (Locale::De, AllEvents) => "Alle Veranstaltungen",
+        (Locale::En, AllEvents) => "All events",
+        (Locale::De, NextDate) => "Nächster Termin: ",
+        (Locale::En, NextDate) => "Next date: ",
+        (Locale::De, UpcomingDates) => "Nächste Termine",
+        (Locale::En, UpcomingDates) => "Upcoming dates",
+        (Locale::De, NoUpcomingDates) => "Zur Zeit sind keine Termine geplant.",
+        (Locale::En, NoUpcomingDates) => "There are no dates planned at the moment.",
+        (Locale::De, NoUpcomingDatesHere) => "Zur Zeit sind hier keine Termine geplant.",
+        (Locale::En, NoUpcomingDatesHere) => "There are no dates planned here at the moment.",
+        (Locale::De, ToBeAnnounced) => "Steht noch nicht fest.",
+        (Locale::En, ToBeAnnounced) => "To be announced.",
+        (Locale::De, Once) => "Einmalig",
+        (Locale::En, Once) => "Once",
+        (Locale::De, RemoveFilter) => "Alle anzeigen",
+        (Locale::En, RemoveFilter) => "Show all",
+        (Locale::De, Preview) => "Vorschau – diese Veranstaltung ist noch nicht veröffentlicht.",
+        (Locale::En, Preview) => "Preview – this event is not published yet.",
+    }
+}
+
+/// The name of the language in that language, to offer switching to it.
+pub fn language_name(locale: Locale) -> &'static str {
+    match locale {
+        Locale::De => "Deutsch",
+        Locale::En => "English",
+    }
+}
+
+pub fn state_name(locale: Locale, state: OccurrenceState) -> &'static str {
+    match (locale, state) {
+        (Locale::De, OccurrenceState::Scheduled) => "Findet statt",
+        (Locale::En, OccurrenceState::Scheduled) => "Scheduled",
+        (Locale::De, OccurrenceState::Cancelled) => "Abgesagt",
+        (Locale::En, OccurrenceState::Cancelled) => "Cancelled",
+        (Locale::De, OccurrenceState::Moved) => "Verlegt",
+        (Locale::En, OccurrenceState::Moved) => "Moved",
+        (Locale::De, OccurrenceState::Tentative) => "Noch nicht bestätigt",
+        (Locale::En, OccurrenceState::Tentative) => "Not confirmed yet",
+    }
+}
+
+pub fn category_name(locale: Locale, category: Category) -> &'static str {
+    match (locale, category) {
+        (Locale::De, Category::Social) => "Social",
+        (Locale::En, Category::Social) => "Social",
+        (Locale::De, Category::Class) => "Kurs",
+        (Locale::En, Category::Class) => "Class",
+        (Locale::De, Category::Workshop) => "Workshop",
+        (Locale::En, Category::Workshop) => "Workshop",
+        (Locale::De, Category::Festival) => "Festival",
+        (Locale::En, Category::Festival) => "Festival",
+    }
+}
+
+pub fn format_date(locale: Locale, date: &NaiveDate) -> String {
+    match locale {
+        Locale::De => {
+            use chrono::Weekday::*;
+
+            let day = match date.weekday() {
+                Mon => "Mo",
+                Tue => "Di",
+                Wed => "Mi",
+                Thu => "Do",
+                Fri => "Fr",
+                Sat => "Sa",
+                Sun => "So",
+            };
+            let format = format!("{}, %d.%m.", day);
+
+            date.format(&format).to_string()
+        }
+        Locale::En => date.format("%a, %b %-d").to_string(),
+    }
+}
+
+pub fn weekday_name(locale: Locale, weekday: Weekday) -> &'static str {
+    use chrono::Weekday::*;
+
+    match (locale, weekday) {
+        (Locale::De, Mon) => "Montag",
+        (Locale::De, Tue) => "Dienstag",
+        (Locale::De, Wed) => "Mittwoch",
+        (Locale::De, Thu) => "Donnerstag",
+        (Locale::De, Fri) => "Freitag",
+        (Locale::De, Sat) => "Samstag",
+        (Locale::De, Sun) => "Sonntag",
+        (Locale::En, Mon) => "Monday",
+        (Locale::En, Tue) => "Tuesday",
+        (Locale::En, Wed) => "Wednesday",
+        (Locale::En, Thu) => "Thursday",
+        (Locale::En, Fri) => "Friday",
+        (Locale::En, Sat) => "Saturday",
+        (Locale::En, Sun) => "Sunday",
+    }
+}
+
+/// Describes a recurrence rule, e. g. "Jeden zweiten Mittwoch" or "Every other Wednesday".
+pub fn describe_frequency(locale: Locale, frequency: Frequency, weekday: Weekday) -> String {
+    let weekday = weekday_name(locale, weekday);
+
+    match (locale, frequency) {
+        (Locale::De, Frequency::Weekly) => format!("Jeden {}", weekday),
+        (Locale::En, Frequency::Weekly) => format!("Every {}", weekday),
+        (Locale::De, Frequency::Biweekly) => format!("Jeden zweiten {}", weekday),
+        (Locale::En, Frequency::Biweekly) => format!("Every other {}", weekday),
+        (Locale::De, Frequency::MonthlyNthWeekday { nth: -1 }) => {
+            format!("Jeden letzten {} im Monat", weekday)
+        }
+        (Locale::En, Frequency::MonthlyNthWeekday { nth: -1 }) => {
+            format!("Every last {} of the month", weekday)
+        }
+        (Locale::De, Frequency::MonthlyNthWeekday { nth }) if nth < 0 => {
+            format!("Jeden {}.-letzten {} im Monat", -nth, weekday)
+        }
+        (Locale::En, Frequency::MonthlyNthWeekday { nth }) if nth < 0 => format!(
+            "Every {}-to-last {} of the month",
+            english_ordinal(-nth),
+            weekday
+        ),
+        (Locale::De, Frequency::MonthlyNthWeekday { nth }) => {
+            format!("Jeden {}. {} im Monat", nth, weekday)
+        }
+        (Locale::En, Frequency::MonthlyNthWeekday { nth }) => {
+            format!("Every {} {} of the month", english_ordinal(nth), weekday)
+        }
+    }
+}
+
+pub fn dates_count(locale: Locale, count: usize) -> String {
+    match locale {
+        Locale::De => format!("{} Termine", count),
+        Locale::En => format!("{} dates", count),
+    }
+}
+
+/// Describes a price, e. g. "Spende (Richtwert 5 €)" or "€15 – €20".
+pub fn format_price(locale: Locale, price: &Price) -> String {
+    let label = match (locale, &price.kind) {
+        (Locale::De, PriceKind::Free) => "Eintritt frei".to_string(),
+        (Locale::En, PriceKind::Free) => "Free".to_string(),
+        (Locale::De, PriceKind::Donation) => "Spende".to_string(),
+        (Locale::En, PriceKind::Donation) => "Donation".to_string(),
+        (Locale::De, PriceKind::Consumption) => "Verzehr".to_string(),
+        (Locale::En, PriceKind::Consumption) => "Buy a drink".to_string(),
+        (_, PriceKind::Fixed { amount, currency }) => format_amount(locale, *amount, currency),
+        (_, PriceKind::Range { min, max, currency }) => format!(
+            "{} – {}",
+            format_amount(locale, *min, currency),
+            format_amount(locale, *max, currency)
+        ),
+    };
+
+    if price.note.trim().is_empty() {
+        label
+    } else {
+        format!("{} ({})", label, price.note.trim())
+    }
+}
+
+/// Formats an amount given in cents, leaving out the cents of whole amounts.
+fn format_amount(locale: Locale, amount: u32, currency: &str) -> String {
+    let symbol = match currency {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        other => other,
+    };
+    let separator = match locale {
+        Locale::De => ",",
+        Locale::En => ".",
+    };
+    let value = if amount % 100 == 0 {
+        (amount / 100).to_string()
+    } else {
+        format!("{}{}{:02}", amount / 100, separator, amount % 100)
+    };
+
+    match locale {
+        Locale::De => format!("{} {}", value, symbol),
+        Locale::En => format!("{}{}", symbol, value),
+    }
+}
+
+fn english_ordinal(n: i8) -> String {
+    let suffix = match n {
+        1 => "st",
+        2 => "nd",
+        3 => "rd",
+        _ => "th",
+    };
+
+    format!("{}{}", n, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_highest_quality() {
+        assert_eq!(preferred_locale("de;q=0.5,en;q=0.9"), Some(Locale::En));
+        assert_eq!(preferred_locale("en;q=0.2, de"), Some(Locale::De));
+        assert_eq!(preferred_locale("de, en"), Some(Locale::De));
+    }
+
+    #[test]
+    fn ignores_region_subtags() {
+        assert_eq!(
+            preferred_locale("en-US,en;q=0.9,de;q=0.8"),
+            Some(Locale::En)
+        );
+        assert_eq!(preferred_locale("de-AT"), Some(Locale::De));
+    }
+
+    #[test]
+    fn falls_back_to_supported_languages() {
+        assert_eq!(
+            preferred_locale("fr-FR,fr;q=0.9,en;q=0.5"),
+            Some(Locale::En)
+        );
+        assert_eq!(preferred_locale("fr, nl;q=0.5"), None);
+        assert_eq!(preferred_locale(""), None);
+        assert_eq!(preferred_locale("de;q=invalid"), Some(Locale::De));
+    }
+
+    #[test]
+    fn links_keep_the_language() {
+        assert_eq!(link(Locale::En, "/"), "/en");
+        assert_eq!(link(Locale::En, "/events/1"), "/en/events/1");
+        assert_eq!(link(Locale::De, "/?tag=balboa"), "/de?tag=balboa");
+    }
+}
@@ -1,6 +1,13 @@
-#![feature(proc_macro_hygiene, decl_macro, custom_attribute)]
+#![feature(proc_macro_hygiene, decl_macro)]
+// Rocket's fairings and guards hand back the whole `Rocket` or request data as their error.
+#![allow(clippy::result_large_err)]
 
 mod api;
+mod auth;
+mod calendar;
+mod feed;
+mod i18n;
+mod markdown;
 mod store;
 
 #[macro_use]
@@ -18,36 +25,339 @@ use std::path::{Path, PathBuf};
 use chrono::prelude::*;
 use maud::{html, Markup, DOCTYPE};
 use rocket::fairing::AdHoc;
-use rocket::response::NamedFile;
+use rocket::http::uri::Uri;
+use rocket::response::{content::Content, NamedFile, Redirect};
 use rocket::State;
+use subtle::ConstantTimeEq;
 
+use api::ApiError;
+use auth::Admin;
+use i18n::Message;
 use store::{
-    Actions, Event, Id, Location, OccurrenceFilter, OccurrenceWithEvent, OccurrenceWithLocation,
-    Store,
+    Actions, Category, Event, EventTranslation, EventWithOccurrences, Id, Locale, Location,
+    OccurrenceFilter, OccurrenceFilterError, OccurrenceState, OccurrenceWithEvent,
+    OccurrenceWithLocation, Store,
 };
 
-#[get("/")]
-fn index(store: Store) -> Markup {
+#[get("/?<tag>&<category>")]
+fn index(store: Store, locale: Locale, tag: Option<String>, category: Option<Category>) -> Markup {
+    let translations = store.event_translations(locale);
+    let filter = OccurrenceFilter {
+        tag,
+        category,
+        ..OccurrenceFilter::upcoming()
+    };
+
+    page(
+        locale,
+        html! {
+            nav.filters {
+                ul.chips {
+                    @for other in Category::ALL.iter() {
+                        li {
+                            a.chip.active[filter.category == Some(*other)] href=( category_link(*other, locale) ) {
+                                ( i18n::category_name(locale, *other) )
+                            }
+                        }
+                    }
+                    @for other in store.all_tags() {
+                        li {
+                            a.chip.tag.active[filter.tag.as_ref() == Some(&other)] href=( tag_link(&other, locale) ) {
+                                ( other )
+                            }
+                        }
+                    }
+                }
+                @if filter.tag.is_some() || filter.category.is_some() {
+                    a.reset href=( i18n::link(locale, "/") ) { ( i18n::text(locale, Message::RemoveFilter) ) }
+                }
+            }
+            ol.schedule {
+                @let locations: HashMap<Id<Location>, Location> = store.all();
+                @for (date, entries) in store.occurrences_by_date(&filter) {
+                    li { ( render_entry(&(date, localize(entries, &translations)), &locations, locale) ) }
+                }
+            }
+        },
+    )
+}
+
+fn category_link(category: Category, locale: Locale) -> String {
+    i18n::link(locale, &format!("/?category={}", category.code()))
+}
+
+fn tag_link(tag: &str, locale: Locale) -> String {
+    i18n::link(locale, &format!("/?tag={}", Uri::percent_encode(tag)))
+}
+
+/// The event's category and tags, linking to the timeline filtered by them.
+fn render_chips(event: &Event, locale: Locale) -> Markup {
+    html! {
+        @if event.current_category().is_some() || event.tags.iter().flatten().next().is_some() {
+            ul.chips {
+                @if let Some(category) = event.current_category() {
+                    li {
+                        a.chip href=( category_link(category, locale) ) {
+                            ( i18n::category_name(locale, category) )
+                        }
+                    }
+                }
+                @for tag in event.tags.iter().flatten() {
+                    li { a.chip.tag href=( tag_link(tag, locale) ) { ( tag ) } }
+                }
+            }
+        }
+    }
+}
+
+/// Replaces the events' texts with their translations, where available.
+fn localize(
+    entries: Vec<OccurrenceWithEvent>,
+    translations: &HashMap<Id<Event>, EventTranslation>,
+) -> Vec<OccurrenceWithEvent> {
+    entries
+        .into_iter()
+        .map(|entry| OccurrenceWithEvent {
+            event: entry.event.localized(translations.get(&entry.event_id)),
+            ..entry
+        })
+        .collect()
+}
+
+#[get("/events")]
+fn events_page(store: Store, locale: Locale) -> Markup {
+    let locations: HashMap<Id<Location>, Location> = store.all();
+    let translations = store.event_translations(locale);
+
+    page(
+        locale,
+        html! {
+            ol.event-list {
+                @for (id, item) in store.events_by_next_occurrence(&OccurrenceFilter::upcoming()) {
+                    @if let Some(next) = item.next_occurrence() {
+                        @let event = item.event.localized(translations.get(&id));
+                        li.event {
+                            h2.title {
+                                a href=( i18n::link(locale, &format!("/events/{}", id)) ) { ( event.title ) }
+                            }
+                            ( render_chips(&event, locale) )
+                            div.quick-info {
+                                ( i18n::text(locale, Message::NextDate) )
+                                ( i18n::format_date(locale, &next.occurrence.start.date()) )
+                                " "
+                                ( next.occurrence.start.format("%H:%M").to_string() )
+                            }
+                            div.frequency { ( describe_frequency(&item, locale) ) }
+                            ul.locations {
+                                @for location_id in event_locations(&item) {
+                                    @if let Some(location) = locations.get(&location_id) {
+                                        li {
+                                            a href=( i18n::link(locale, &format!("/locations/{}", location_id)) ) { ( location.name ) }
+                                        }
+                                    }
+                                }
+                            }
+                            div.teaser { ( markdown::markup(&event.teaser) ) }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Summarizes how often the event takes place, e. g. "Jeden zweiten Mittwoch".
+fn describe_frequency(item: &EventWithOccurrences, locale: Locale) -> String {
+    let rules: Vec<String> = item
+        .recurrences
+        .iter()
+        .flatten()
+        .map(|recurrence| {
+            i18n::describe_frequency(
+                locale,
+                recurrence.frequency,
+                recurrence.first.occurrence.start.weekday(),
+            )
+        })
+        .collect();
+
+    if !rules.is_empty() {
+        rules.join(", ")
+    } else if item.occurrences.len() == 1 {
+        i18n::text(locale, Message::Once).to_string()
+    } else {
+        i18n::dates_count(locale, item.occurrences.len())
+    }
+}
+
+/// The locations of the event's occurrences, in the order they are first visited.
+fn event_locations(item: &EventWithOccurrences) -> Vec<Id<Location>> {
+    let mut location_ids: Vec<Id<Location>> = Vec::new();
+    for occurrence in &item.occurrences {
+        if !location_ids.contains(&occurrence.location_id) {
+            location_ids.push(occurrence.location_id.clone());
+        }
+    }
+
+    location_ids
+}
+
+#[get("/events/<id>")]
+fn event_page(store: Store, locale: Locale, id: Id<Event>) -> Option<Markup> {
+    render_event_page(&store, locale, id, false)
+}
+
+/// Shows the event before it is published to whoever got the link with its preview token.
+/// The token is compared in constant time, so response times do not reveal how much of it matches.
+#[get("/events/<id>/preview?<token>")]
+fn event_preview(store: Store, locale: Locale, id: Id<Event>, token: String) -> Option<Markup> {
+    let expected = store.preview_token(id.clone()).ok()?;
+    if !bool::from(expected.as_bytes().ct_eq(token.as_bytes())) {
+        return None;
+    }
+
+    render_event_page(&store, locale, id, true)
+}
+
+fn render_event_page(
+    store: &Store,
+    locale: Locale,
+    id: Id<Event>,
+    preview: bool,
+) -> Option<Markup> {
+    let locations: HashMap<Id<Location>, Location> = store.all();
+    let EventWithOccurrences {
+        event,
+        occurrences,
+        translations,
+        ..
+    } = store
+        .read_event_with_occurrences(id, &OccurrenceFilter::upcoming())
+        .ok()?;
+    if !preview && !event.is_published() {
+        return None;
+    }
+    let event = event.localized(translations.unwrap_or_default().get(&locale));
+
+    Some(page(
+        locale,
+        html! {
+            article.event-page {
+                @if preview {
+                    p.preview-notice { ( i18n::text(locale, Message::Preview) ) }
+                }
+                h2.title { ( event.title ) }
+                ( render_chips(&event, locale) )
+                div.teaser { ( markdown::markup(&event.teaser) ) }
+                @if let Some(price) = event.current_price() {
+                    div.price { ( i18n::format_price(locale, price) ) }
+                }
+                div.description { ( markdown::markup(&event.description) ) }
+                h3 { ( i18n::text(locale, Message::UpcomingDates) ) }
+                @if occurrences.is_empty() {
+                    p { ( i18n::text(locale, Message::NoUpcomingDates) ) }
+                } @else {
+                    ol.occurrences {
+                        @for occurrence in &occurrences {
+                            @let entry_html = html_from_occurrence(occurrence, &event, &locations, locale);
+                            li class=( occurrence.current_status().state.code() ) {
+                                span.date { ( i18n::format_date(locale, &occurrence.occurrence.start.date()) ) }
+                                " "
+                                span.quick-info { ( entry_html.quick_info ) }
+                                @let title_suffix = occurrence.current_overrides().title_suffix;
+                                @if !title_suffix.trim().is_empty() {
+                                    " " span.title-suffix { ( title_suffix ) }
+                                }
+                                ( entry_html.status )
+                                ( entry_html.note )
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    ))
+}
+
+#[get("/locations/<id>")]
+fn location_page(store: Store, locale: Locale, id: Id<Location>) -> Option<Markup> {
+    let location: Location = store.read(id.clone()).ok()?;
+    let occurrences = store.occurrences_by_date_at(&id, &OccurrenceFilter::upcoming());
+    let mut locations: HashMap<Id<Location>, Location> = HashMap::new();
+    locations.insert(id, location.clone());
+    let translations = store.event_translations(locale);
+    let map_link = format!(
+        "https://www.openstreetmap.org/search?query={}",
+        Uri::percent_encode(&location.address)
+    );
+
+    Some(page(
+        locale,
+        html! {
+            article.location-page {
+                h2.title { ( location.name ) }
+                a.address href=( map_link ) { ( location.address ) }
+                h3 { ( i18n::text(locale, Message::UpcomingDates) ) }
+                @if occurrences.is_empty() {
+                    p { ( i18n::text(locale, Message::NoUpcomingDatesHere) ) }
+                }
+            }
+            ol.schedule {
+                @for (date, entries) in occurrences {
+                    li { ( render_entry(&(date, localize(entries, &translations)), &locations, locale) ) }
+                }
+            }
+        },
+    ))
+}
+
+/// Published content pages, e. g. `/get-started`. Any other route takes precedence.
+#[get("/<slug>", rank = 10)]
+fn content_page(store: Store, locale: Locale, slug: String) -> Option<Markup> {
+    let content = store
+        .published_page(&slug)
+        .expect("Loading from database failed.")?;
+
+    Some(page(
+        locale,
+        html! {
+            article.content-page {
+                h2.title { ( content.title ) }
+                ( markdown::markup(&content.body) )
+            }
+        },
+    ))
+}
+
+fn page(locale: Locale, content: Markup) -> Markup {
     html! {
         ( DOCTYPE )
-        html lang="de" {
+        html lang=( locale.code() ) {
             head {
                 meta name="viewport" content="width=device-width, initial-scale=1";
 
-                link href="static/main.css" rel="stylesheet";
+                link href="/static/main.css" rel="stylesheet";
+                link href="/feed.atom" rel="alternate" type="application/atom+xml" title="Lindy Hop Aachen";
             }
             body {
                 header {
-                    h1 { "Lindy Hop Aachen" }
-                }
-                main {
-                    ol.schedule {
-                        @let locations: HashMap<Id<Location>, Location> = store.all();
-                        @for occurrences_for_date in store.occurrences_by_date(&OccurrenceFilter::upcoming()) {
-                            li { ( render_entry(&occurrences_for_date, &locations) ) }
+                    h1 { a href=( i18n::link(locale, "/") ) { "Lindy Hop Aachen" } }
+                    nav {
+                        a href=( i18n::link(locale, "/events") ) { ( i18n::text(locale, Message::AllEvents) ) }
+                        ul.languages {
+                            @for other in Locale::ALL.iter().filter(|other| **other != locale) {
+                                li {
+                                    a href=( i18n::link(*other, "/") ) lang=( other.code() ) {
+                                        ( i18n::language_name(*other) )
+                                    }
+                                }
+                            }
                         }
                     }
                 }
+                main {
+                    ( content )
+                }
             }
         }
     }
@@ -56,45 +366,37 @@ fn index(store: Store) -> Markup {
 fn render_entry(
     (date, entries): &(NaiveDate, Vec<OccurrenceWithEvent>),
     locations: &HashMap<Id<Location>, Location>,
+    locale: Locale,
 ) -> Markup {
     html! {
-        div.date { ( format_date(date) ) }
+        div.date { ( i18n::format_date(locale, date) ) }
         ol.events {
             @for occurrence_entry in entries {
-                li.event { ( render_occurrence(occurrence_entry, locations) ) }
+                li class=( format!("event {}", occurrence_entry.occurrence.current_status().state.code()) ) {
+                    ( render_occurrence(occurrence_entry, locations, locale) )
+                }
             }
         }
     }
 }
 
-fn format_date(date: &NaiveDate) -> String {
-    use chrono::Weekday::*;
-
-    let day = match date.weekday() {
-        Mon => "Mo",
-        Tue => "Di",
-        Wed => "Mi",
-        Thu => "Do",
-        Fri => "Fr",
-        Sat => "Sa",
-        Sun => "So",
-    };
-    let format = format!("{}, %d.%m.", day);
-
-    date.format(&format).to_string()
-}
-
 fn render_occurrence(
     entry: &OccurrenceWithEvent,
     locations: &HashMap<Id<Location>, Location>,
+    locale: Locale,
 ) -> Markup {
     html! {
-        @let entry_html =  html_from_occurrence(&entry.occurrence, &entry.event, locations);
+        @let entry_html =  html_from_occurrence(&entry.occurrence, &entry.event, locations, locale);
         div.quick-info { ( entry_html.quick_info ) }
-        h2.title { ( entry_html.title ) }
+        ( entry_html.status )
+        h2.title {
+            a href=( i18n::link(locale, &format!("/events/{}", entry.event_id)) ) { ( entry_html.title ) }
+        }
+        ( render_chips(&entry.event, locale) )
         div.content {
             div.description {
                 div.teaser { ( entry_html.teaser ) }
+                ( entry_html.note )
             }
         }
     }
@@ -103,47 +405,133 @@ fn render_occurrence(
 struct OccurrenceHtml {
     title: Markup,
     quick_info: Markup,
+    /// A notice if the occurrence does not take place as planned.
+    status: Markup,
     teaser: Markup,
+    /// Details that only apply to this occurrence.
+    note: Markup,
 }
 
 fn html_from_occurrence(
     occurrence: &OccurrenceWithLocation,
     event: &Event,
     locations: &HashMap<Id<Location>, Location>,
+    locale: Locale,
 ) -> OccurrenceHtml {
+    let event = occurrence.apply_overrides(event);
+    let note = occurrence.current_overrides().note;
     let maybe_location = locations.get(&occurrence.location_id);
-    let location_name = match maybe_location {
-        Some(location) => &location.name,
-        None => "Steht noch nicht fest.",
+    let location_html = match maybe_location {
+        Some(location) => html! {
+            a href=( i18n::link(locale, &format!("/locations/{}", occurrence.location_id)) ) { ( location.name ) }
+        },
+        None => html! { ( i18n::text(locale, Message::ToBeAnnounced) ) },
+    };
+
+    let status = occurrence.current_status();
+    let status_html = html! {
+        @if status.state != OccurrenceState::Scheduled {
+            div.status {
+                strong { ( i18n::state_name(locale, status.state) ) }
+                @if !status.note.trim().is_empty() {
+                    ": " ( status.note )
+                }
+            }
+        }
     };
 
     OccurrenceHtml {
         title: html! { ( event.title ) },
-        quick_info: html! { ( format!("{} - {}", occurrence.occurrence.start.format("%H:%M"), location_name) ) },
-        teaser: html! { ( event.teaser ) },
+        status: status_html,
+        quick_info: html! {
+            ( occurrence.occurrence.start.format("%H:%M").to_string() ) " - " ( location_html )
+            @if let Some(price) = event.current_price() {
+                " - " span.price { ( i18n::format_price(locale, price) ) }
+            }
+        },
+        teaser: markdown::markup(&event.teaser),
+        note: html! {
+            @if !note.trim().is_empty() {
+                div.note { ( note ) }
+            }
+        },
     }
 }
 
+/// Without `before` or `after`, the feed lists the upcoming occurrences, as subscribed calendars
+/// would otherwise have to load the whole history. Invalid filters are answered like in the API.
+#[get("/calendar.ics?<filter..>")]
+fn calendar_feed(
+    store: Store,
+    filter: Result<OccurrenceFilter, OccurrenceFilterError>,
+) -> Result<Content<String>, ApiError> {
+    let filter = filter?;
+    let filter = if filter.before.is_none() && filter.after.is_none() {
+        OccurrenceFilter {
+            after: OccurrenceFilter::upcoming().after,
+            ..filter
+        }
+    } else {
+        filter
+    };
+    let locations: HashMap<Id<Location>, Location> = store.all();
+    let occurrences: Vec<OccurrenceWithEvent> = store
+        .occurrences_by_date(&filter)
+        .into_iter()
+        .flat_map(|(_, entries)| entries)
+        .collect();
+
+    Ok(calendar::render(&occurrences, &locations))
+}
+
+#[get("/feed.atom")]
+fn atom_feed(store: Store, site_url: State<SiteUrl>) -> Content<String> {
+    let now = chrono::Local::now().naive_local();
+    let filter = OccurrenceFilter {
+        before: Some(now + chrono::Duration::days(feed::UPCOMING_DAYS)),
+        ..OccurrenceFilter::upcoming()
+    };
+
+    feed::render(feed::Feed {
+        site_url: &site_url.0,
+        new_events: store
+            .events_published_since(now - chrono::Duration::days(feed::NEW_EVENT_DAYS)),
+        entries: store
+            .occurrences_by_date(&filter)
+            .into_iter()
+            .flat_map(|(_, entries)| entries)
+            .collect(),
+        locations: store.all(),
+    })
+}
+
 #[get("/admin")]
-fn admin_route() -> Option<NamedFile> {
-    admin()
+fn admin_route(admin: Option<Admin>) -> Result<Option<NamedFile>, Redirect> {
+    serve_admin(admin)
 }
 
 // We also want to serve the file when subroutes are called, e. g. `/admin/event/42`.
 // Removing this would break reloading the admin on subroutes.
 #[get("/admin/<path..>")]
 #[allow(unused_variables)]
-fn admin_subroute(path: PathBuf) -> Option<NamedFile> {
-    admin()
+fn admin_subroute(path: PathBuf, admin: Option<Admin>) -> Result<Option<NamedFile>, Redirect> {
+    serve_admin(admin)
 }
 
-fn admin() -> Option<NamedFile> {
-    NamedFile::open(Path::new("admin/dist/index.html")).ok()
+fn serve_admin(admin: Option<Admin>) -> Result<Option<NamedFile>, Redirect> {
+    match admin {
+        Some(_) => Ok(NamedFile::open(Path::new("admin/dist/index.html")).ok()),
+        None => Err(Redirect::to("/login")),
+    }
 }
 
 #[derive(Debug)]
 struct AssetsDir(PathBuf);
 
+/// The address of the website, to link to it from feeds.
+#[derive(Debug)]
+struct SiteUrl(String);
+
 #[get("/static/<file..>")]
 fn static_file(file: PathBuf, assets_dir: State<AssetsDir>) -> Option<NamedFile> {
     let path = assets_dir.0.join(file);
@@ -152,8 +540,10 @@ fn static_file(file: PathBuf, assets_dir: State<AssetsDir>) -> Option<NamedFile>
 }
 
 fn main() {
-    let rocket = rocket::ignite()
+    let mut rocket = rocket::ignite()
         .attach(Store::fairing())
+        .attach(auth::fairing())
+        .attach(Store::trash_fairing())
         .attach(AdHoc::on_attach("Assets Config", |rocket| {
             let assets_dir = PathBuf::from(rocket.config().get_str("assets_dir").unwrap_or("."));
             if assets_dir.exists() {
@@ -167,9 +557,40 @@ fn main() {
                 Err(rocket)
             }
         }))
+        .attach(AdHoc::on_attach("Site Config", |rocket| {
+            let site_url = rocket
+                .config()
+                .get_str("site_url")
+                .unwrap_or("http://localhost:8000")
+                .trim_end_matches('/')
+                .to_string();
+
+            Ok(rocket.manage(SiteUrl(site_url)))
+        }))
         .mount(
             "/",
-            routes![static_file, index, admin_route, admin_subroute],
+            routes![
+                static_file,
+                calendar_feed,
+                atom_feed,
+                admin_route,
+                admin_subroute
+            ],
+        )
+        .mount("/", auth::routes());
+    // The public pages are also available with a language prefix, e. g. `/en/events`.
+    for prefix in &["/", "/de", "/en"] {
+        rocket = rocket.mount(
+            prefix,
+            routes![
+                index,
+                events_page,
+                event_page,
+                event_preview,
+                location_page,
+                content_page
+            ],
         );
+    }
     api::mount(rocket, "/api").launch();
 }
@@ -0,0 +1,15 @@
+use maud::{Markup, PreEscaped};
+use pulldown_cmark::{html, Parser};
+
+/// Renders Markdown to HTML. Anything that could run scripts or break the page is removed,
+/// because the source is written in the admin.
+pub fn render(source: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new(source));
+
+    ammonia::clean(&unsafe_html)
+}
+
+pub fn markup(source: &str) -> Markup {
+    PreEscaped(render(source))
+}
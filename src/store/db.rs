@@ -2,11 +2,29 @@ use std::fmt::Debug;
 
 use diesel::{self, prelude::*};
 use rocket::Rocket;
+use rocket_contrib::databases::{r2d2, Poolable};
 use uuid::Uuid;
 
 #[database("sqlite_database")]
 pub struct Connection(SqliteConnection);
 
+/// The pool behind `Connection`, to take connections outside of requests, e. g. in a thread.
+#[derive(Clone)]
+pub struct Pool(r2d2::Pool<<SqliteConnection as Poolable>::Manager>);
+
+impl Pool {
+    pub fn of(rocket: &Rocket) -> Option<Pool> {
+        rocket
+            .state::<ConnectionPool>()
+            .map(|pool| Pool(pool.0.clone()))
+    }
+
+    /// Waits for a free connection, failing after the pool's timeout.
+    pub fn get(&self) -> Option<Connection> {
+        self.0.get().ok().map(Connection)
+    }
+}
+
 embed_migrations!();
 
 pub fn initialize(rocket: Rocket) -> Result<Rocket, Rocket> {
@@ -21,6 +39,16 @@ pub fn initialize(rocket: Rocket) -> Result<Rocket, Rocket> {
     }
 }
 
+/// Timestamps are stored as local times, like the occurrences.
+pub fn now() -> NaiveDateTime {
+    chrono::Local::now().naive_local()
+}
+
+/// SQLite only enforces foreign keys if they are enabled for each connection.
+pub fn enable_foreign_keys(conn: &SqliteConnection) -> QueryResult<()> {
+    diesel::Connection::execute(conn, "PRAGMA foreign_keys = ON").map(|_| ())
+}
+
 pub mod schema {
     table! {
         events {
@@ -28,6 +56,31 @@ pub mod schema {
             title -> Text,
             teaser -> Text,
             description -> Text,
+            price -> Nullable<Text>,
+            category -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            updated_by -> Nullable<Binary>,
+            deleted_at -> Nullable<Timestamp>,
+            publication -> Text,
+            publish_at -> Nullable<Timestamp>,
+            preview_token -> Text,
+            published_at -> Nullable<Timestamp>,
+        }
+    }
+    table! {
+        tags (event_id, name) {
+            event_id -> Binary,
+            name -> Text,
+        }
+    }
+    table! {
+        event_translations (event_id, language) {
+            event_id -> Binary,
+            language -> Text,
+            title -> Text,
+            teaser -> Text,
+            description -> Text,
         }
     }
     table! {
@@ -37,6 +90,27 @@ pub mod schema {
             start -> Timestamp,
             duration -> Integer,
             location_id -> Binary,
+            status -> Text,
+            status_note -> Text,
+            title_suffix -> Text,
+            teaser_override -> Text,
+            note -> Text,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            updated_by -> Nullable<Binary>,
+        }
+    }
+    table! {
+        recurrences {
+            id -> Binary,
+            event_id -> Binary,
+            start -> Timestamp,
+            duration -> Integer,
+            location_id -> Binary,
+            frequency -> Text,
+            until -> Nullable<Date>,
+            count -> Nullable<Integer>,
+            exceptions -> Text,
         }
     }
     table! {
@@ -44,6 +118,50 @@ pub mod schema {
             id -> Binary,
             name -> Text,
             address -> Text,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            updated_by -> Nullable<Binary>,
+            deleted_at -> Nullable<Timestamp>,
+        }
+    }
+    // Allows to filter by subselects on other tables, e. g. the locations of an event's occurrences.
+    allow_tables_to_appear_in_same_query!(events, occurrences, recurrences, locations);
+
+    table! {
+        pages {
+            id -> Binary,
+            slug -> Text,
+            title -> Text,
+            body -> Text,
+            published -> Bool,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            updated_by -> Nullable<Binary>,
+        }
+    }
+    table! {
+        users {
+            id -> Binary,
+            name -> Text,
+            password_hash -> Text,
+        }
+    }
+    table! {
+        invitations (token) {
+            token -> Text,
+            user_id -> Binary,
+            expires_at -> Timestamp,
+        }
+    }
+    table! {
+        revisions {
+            id -> Binary,
+            item_kind -> Text,
+            item_id -> Binary,
+            action -> Text,
+            snapshot -> Text,
+            created_at -> Timestamp,
+            created_by -> Nullable<Binary>,
         }
     }
 }
@@ -56,15 +174,31 @@ use diesel::backend::Backend;
 use diesel::deserialize;
 use diesel::expression::{bound::Bound, AsExpression};
 use diesel::serialize::{self, Output};
-use diesel::sql_types::{Binary, HasSqlType};
+use diesel::sql_types::{Binary, HasSqlType, Nullable};
 use diesel::sqlite::Sqlite;
 use diesel::types::{FromSql, ToSql};
 use schema::*;
 
 // SqlId implementation inspired by https://github.com/forte-music/core/blob/fc9cd6217708b0dd6ae684df3a53276804479c59/src/models/id.rs#L67
-#[derive(Debug, Deserialize, FromSqlRow, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Deserialize, FromSqlRow, Clone)]
 pub struct SqlId<Item>(Uuid, PhantomData<Item>);
 
+// Implemented by hand, because deriving would require `Item` to implement them as well, which
+// e. g. `Revision` does not.
+impl<Item> PartialEq for SqlId<Item> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<Item> Eq for SqlId<Item> {}
+
+impl<Item> std::hash::Hash for SqlId<Item> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
 impl<Item> From<Uuid> for SqlId<Item> {
     fn from(uuid: Uuid) -> Self {
         SqlId(uuid, PhantomData)
@@ -113,15 +247,58 @@ impl<'a, Item> AsExpression<Binary> for &'a SqlId<Item> {
     }
 }
 
+// Optional references, e. g. `updated_by`, are stored in nullable columns.
+impl<DB: Backend + HasSqlType<Binary>, Item: Debug> ToSql<Nullable<Binary>, DB> for SqlId<Item> {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        <SqlId<Item> as ToSql<Binary, DB>>::to_sql(self, out)
+    }
+}
+
+impl<Item> AsExpression<Nullable<Binary>> for SqlId<Item> {
+    type Expression = Bound<Nullable<Binary>, SqlId<Item>>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<'a, Item> AsExpression<Nullable<Binary>> for &'a SqlId<Item> {
+    type Expression = Bound<Nullable<Binary>, &'a SqlId<Item>>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+/// Updating with `None` clears the column, so values to keep have to be copied from the stored event.
 #[derive(Queryable, Insertable, Debug, Identifiable, Clone, PartialEq, AsChangeset)]
 #[table_name = "events"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct SqlEvent {
     pub id: SqlId<Event>,
     pub title: String,
     pub teaser: String,
     pub description: String,
+    /// JSON encoded `Price`.
+    pub price: Option<String>,
+    /// JSON encoded `Category`.
+    pub category: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub updated_by: Option<SqlId<User>>,
+    /// Set for events in the trash.
+    pub deleted_at: Option<NaiveDateTime>,
+    /// The code of the `Publication`.
+    pub publication: String,
+    /// Only used by scheduled publications.
+    pub publish_at: Option<NaiveDateTime>,
+    /// Secret part of the link that shows the event before it is published.
+    pub preview_token: String,
+    /// When the event became or becomes public, `None` for drafts.
+    pub published_at: Option<NaiveDateTime>,
 }
 
+/// The conversion leaves out the tags, which are stored separately.
 impl From<SqlEvent> for (super::Id<Event>, Event) {
     fn from(event: SqlEvent) -> Self {
         (
@@ -130,6 +307,23 @@ impl From<SqlEvent> for (super::Id<Event>, Event) {
                 title: event.title,
                 teaser: event.teaser,
                 description: event.description,
+                price: Some(event.price.map(|price| {
+                    serde_json::from_str(&price).expect("Invalid price in database.")
+                })),
+                category: Some(event.category.map(|category| {
+                    serde_json::from_str(&category).expect("Invalid category in database.")
+                })),
+                tags: None,
+                publication: Some(
+                    Publication::from_code(&event.publication, event.publish_at)
+                        .expect("Invalid publication in database."),
+                ),
+                audit: Some(Audit {
+                    created_at: event.created_at,
+                    updated_at: event.updated_at,
+                    updated_by: event.updated_by.map(Into::into),
+                    published_at: event.published_at,
+                }),
             },
         )
     }
@@ -138,16 +332,85 @@ impl From<SqlEvent> for (super::Id<Event>, Event) {
 impl From<Event> for SqlEvent {
     fn from(event: Event) -> SqlEvent {
         let id = Uuid::new_v4();
+        let publication = event.publication.unwrap_or_default();
+        let price = event
+            .current_price()
+            .map(|price| serde_json::to_string(price).unwrap());
+        let category = event
+            .current_category()
+            .map(|category| serde_json::to_string(&category).unwrap());
 
         SqlEvent {
             id: id.into(),
             title: event.title,
             teaser: event.teaser,
             description: event.description,
+            price,
+            category,
+            created_at: now(),
+            updated_at: now(),
+            updated_by: None,
+            deleted_at: None,
+            publication: publication.code().to_string(),
+            publish_at: publication.publish_at(),
+            preview_token: thread_rng().sample_iter(&Alphanumeric).take(32).collect(),
+            published_at: match publication {
+                Publication::Draft => None,
+                Publication::Scheduled { publish_at } => Some(publish_at),
+                Publication::Published => Some(now()),
+            },
         }
     }
 }
 
+#[derive(Queryable, Insertable, Clone, Debug, Identifiable, Associations)]
+#[belongs_to(SqlEvent, foreign_key = "event_id")]
+#[table_name = "event_translations"]
+#[primary_key(event_id, language)]
+pub struct SqlEventTranslation {
+    pub event_id: SqlId<Event>,
+    pub language: String,
+    pub title: String,
+    pub teaser: String,
+    pub description: String,
+}
+
+impl From<SqlEventTranslation> for (Locale, EventTranslation) {
+    fn from(translation: SqlEventTranslation) -> Self {
+        (
+            Locale::from_code(&translation.language).expect("Invalid language in database."),
+            EventTranslation {
+                title: translation.title,
+                teaser: translation.teaser,
+                description: translation.description,
+            },
+        )
+    }
+}
+
+impl From<(Locale, EventTranslation, SqlId<Event>)> for SqlEventTranslation {
+    fn from(
+        (locale, translation, event_id): (Locale, EventTranslation, SqlId<Event>),
+    ) -> SqlEventTranslation {
+        SqlEventTranslation {
+            event_id,
+            language: locale.code().to_string(),
+            title: translation.title,
+            teaser: translation.teaser,
+            description: translation.description,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Clone, Debug, Identifiable, Associations)]
+#[belongs_to(SqlEvent, foreign_key = "event_id")]
+#[table_name = "tags"]
+#[primary_key(event_id, name)]
+pub struct SqlTag {
+    pub event_id: SqlId<Event>,
+    pub name: String,
+}
+
 #[derive(
     Queryable, Insertable, Clone, Debug, Identifiable, PartialEq, AsChangeset, Associations,
 )]
@@ -160,6 +423,14 @@ pub struct SqlOccurrence {
     pub start: NaiveDateTime,
     pub duration: i32,
     pub location_id: SqlId<Location>,
+    pub status: String,
+    pub status_note: String,
+    pub title_suffix: String,
+    pub teaser_override: String,
+    pub note: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub updated_by: Option<SqlId<User>>,
 }
 
 impl From<SqlOccurrence> for (Id<Occurrence>, OccurrenceWithLocation) {
@@ -167,11 +438,28 @@ impl From<SqlOccurrence> for (Id<Occurrence>, OccurrenceWithLocation) {
         (
             occurrence.id.0.into(),
             (OccurrenceWithLocation {
+                id: Some(occurrence.id.clone().into()),
                 occurrence: Occurrence {
                     start: occurrence.start,
                     duration: occurrence.duration as u32,
                 },
                 location_id: occurrence.location_id.into(),
+                status: Some(OccurrenceStatus {
+                    state: OccurrenceState::from_code(&occurrence.status)
+                        .expect("Invalid status in database."),
+                    note: occurrence.status_note,
+                }),
+                overrides: Some(OccurrenceOverrides {
+                    title_suffix: occurrence.title_suffix,
+                    teaser: occurrence.teaser_override,
+                    note: occurrence.note,
+                }),
+                audit: Some(Audit {
+                    created_at: occurrence.created_at,
+                    updated_at: occurrence.updated_at,
+                    updated_by: occurrence.updated_by.map(Into::into),
+                    published_at: None,
+                }),
             }),
         )
     }
@@ -181,13 +469,19 @@ impl From<(OccurrenceWithLocation, SqlId<Event>)> for SqlOccurrence {
     fn from(
         (
             OccurrenceWithLocation {
+                id,
                 occurrence,
                 location_id,
+                status,
+                overrides,
+                ..
             },
             event_id,
         ): (OccurrenceWithLocation, SqlId<Event>),
     ) -> SqlOccurrence {
-        let id = Uuid::new_v4();
+        let id = id.map_or_else(Uuid::new_v4, |id| id.id);
+        let status = status.unwrap_or_default();
+        let overrides = overrides.unwrap_or_default();
 
         SqlOccurrence {
             id: id.into(),
@@ -195,6 +489,73 @@ impl From<(OccurrenceWithLocation, SqlId<Event>)> for SqlOccurrence {
             duration: occurrence.duration as i32,
             location_id: location_id.into(),
             event_id,
+            status: status.state.code().to_string(),
+            status_note: status.note,
+            title_suffix: overrides.title_suffix,
+            teaser_override: overrides.teaser,
+            note: overrides.note,
+            created_at: now(),
+            updated_at: now(),
+            updated_by: None,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Clone, Debug, Identifiable, Associations)]
+#[belongs_to(SqlEvent, foreign_key = "event_id")]
+#[table_name = "recurrences"]
+pub struct SqlRecurrence {
+    pub id: SqlId<Recurrence>,
+    pub event_id: SqlId<Event>,
+    pub start: NaiveDateTime,
+    pub duration: i32,
+    pub location_id: SqlId<Location>,
+    /// JSON encoded `Frequency`.
+    pub frequency: String,
+    pub until: Option<NaiveDate>,
+    pub count: Option<i32>,
+    /// JSON encoded list of dates.
+    pub exceptions: String,
+}
+
+impl From<SqlRecurrence> for (Id<Recurrence>, Recurrence) {
+    fn from(recurrence: SqlRecurrence) -> Self {
+        (
+            recurrence.id.clone().into(),
+            Recurrence {
+                id: Some(recurrence.id.into()),
+                first: FirstOccurrence {
+                    occurrence: Occurrence {
+                        start: recurrence.start,
+                        duration: recurrence.duration as u32,
+                    },
+                    location_id: recurrence.location_id.into(),
+                },
+                frequency: serde_json::from_str(&recurrence.frequency)
+                    .expect("Invalid frequency in database."),
+                until: recurrence.until,
+                count: recurrence.count.map(|count| count as u32),
+                exceptions: serde_json::from_str(&recurrence.exceptions)
+                    .expect("Invalid exceptions in database."),
+            },
+        )
+    }
+}
+
+impl From<(Recurrence, SqlId<Event>)> for SqlRecurrence {
+    fn from((recurrence, event_id): (Recurrence, SqlId<Event>)) -> SqlRecurrence {
+        let id = recurrence.id.map_or_else(Uuid::new_v4, |id| id.id);
+
+        SqlRecurrence {
+            id: id.into(),
+            event_id,
+            start: recurrence.first.occurrence.start,
+            duration: recurrence.first.occurrence.duration as i32,
+            location_id: recurrence.first.location_id.into(),
+            frequency: serde_json::to_string(&recurrence.frequency).unwrap(),
+            until: recurrence.until,
+            count: recurrence.count.map(|count| count as i32),
+            exceptions: serde_json::to_string(&recurrence.exceptions).unwrap(),
         }
     }
 }
@@ -205,6 +566,11 @@ pub struct SqlLocation {
     pub id: SqlId<Location>,
     pub name: String,
     pub address: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub updated_by: Option<SqlId<User>>,
+    /// Set for locations in the trash. Updating with `None` keeps them there.
+    pub deleted_at: Option<NaiveDateTime>,
 }
 impl From<Location> for SqlLocation {
     fn from(location: Location) -> SqlLocation {
@@ -214,6 +580,10 @@ impl From<Location> for SqlLocation {
             id: id.into(),
             name: location.name,
             address: location.address,
+            created_at: now(),
+            updated_at: now(),
+            updated_by: None,
+            deleted_at: None,
         }
     }
 }
@@ -224,6 +594,128 @@ impl From<SqlLocation> for (Id<Location>, Location) {
             Location {
                 name: location.name,
                 address: location.address,
+                audit: Some(Audit {
+                    created_at: location.created_at,
+                    updated_at: location.updated_at,
+                    updated_by: location.updated_by.map(Into::into),
+                    published_at: None,
+                }),
+            },
+        )
+    }
+}
+
+#[derive(Queryable, Clone, Identifiable, Insertable, Debug, AsChangeset)]
+#[table_name = "pages"]
+pub struct SqlPage {
+    pub id: SqlId<Page>,
+    pub slug: String,
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub updated_by: Option<SqlId<User>>,
+}
+impl From<Page> for SqlPage {
+    fn from(page: Page) -> SqlPage {
+        let id = Uuid::new_v4();
+
+        SqlPage {
+            id: id.into(),
+            slug: page.slug,
+            title: page.title,
+            body: page.body,
+            published: page.published,
+            created_at: now(),
+            updated_at: now(),
+            updated_by: None,
+        }
+    }
+}
+impl From<SqlPage> for (Id<Page>, Page) {
+    fn from(page: SqlPage) -> (Id<Page>, Page) {
+        (
+            page.id.into(),
+            Page {
+                slug: page.slug,
+                title: page.title,
+                body: page.body,
+                published: page.published,
+                audit: Some(Audit {
+                    created_at: page.created_at,
+                    updated_at: page.updated_at,
+                    updated_by: page.updated_by.map(Into::into),
+                    published_at: None,
+                }),
+            },
+        )
+    }
+}
+
+#[derive(Queryable, Clone, Identifiable, Insertable, Debug, AsChangeset)]
+#[table_name = "users"]
+pub struct SqlUser {
+    pub id: SqlId<User>,
+    pub name: String,
+    pub password_hash: String,
+}
+impl From<User> for SqlUser {
+    /// Users without a password hash cannot log in until they accept an invitation.
+    fn from(user: User) -> SqlUser {
+        let id = Uuid::new_v4();
+
+        SqlUser {
+            id: id.into(),
+            name: user.name,
+            password_hash: String::new(),
+        }
+    }
+}
+impl From<SqlUser> for (Id<User>, User) {
+    fn from(user: SqlUser) -> (Id<User>, User) {
+        (user.id.into(), User { name: user.name })
+    }
+}
+
+#[derive(Queryable, Insertable, Clone, Debug, Identifiable, Associations)]
+#[belongs_to(SqlUser, foreign_key = "user_id")]
+#[primary_key(token)]
+#[table_name = "invitations"]
+pub struct SqlInvitation {
+    pub token: String,
+    pub user_id: SqlId<User>,
+    pub expires_at: NaiveDateTime,
+}
+
+/// The `item_kind` of event revisions, whose snapshots are `EventWithOccurrences`.
+pub const EVENT_REVISION: &str = "event";
+/// The `item_kind` of location revisions.
+pub const LOCATION_REVISION: &str = "location";
+
+#[derive(Queryable, Insertable, Clone, Debug, Identifiable)]
+#[table_name = "revisions"]
+pub struct SqlRevision {
+    pub id: SqlId<Revision>,
+    pub item_kind: String,
+    /// The id of the event or location, which may have been deleted since.
+    pub item_id: Vec<u8>,
+    pub action: String,
+    pub snapshot: String,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<SqlId<User>>,
+}
+impl From<SqlRevision> for (Id<Revision>, Revision) {
+    fn from(revision: SqlRevision) -> (Id<Revision>, Revision) {
+        (
+            revision.id.into(),
+            Revision {
+                action: RevisionAction::from_code(&revision.action)
+                    .expect("Invalid revision action in database."),
+                snapshot: serde_json::from_str(&revision.snapshot)
+                    .expect("Invalid revision snapshot in database."),
+                created_at: revision.created_at,
+                created_by: revision.created_by.map(Into::into),
             },
         )
     }
@@ -1,25 +1,41 @@
 mod db;
 mod model;
+mod validation;
+
+#[cfg(test)]
+mod tests;
 
 use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::io::Cursor;
 use std::marker::PhantomData;
+use std::thread;
+use std::time::Duration;
 
 use chrono::{NaiveDate, NaiveDateTime};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use rocket::fairing::AdHoc;
 use rocket::http::RawStr;
 use rocket::http::Status;
-use rocket::request::{FormItem, FromParam, FromQuery, FromRequest, Outcome, Query, Request};
+use rocket::request::{
+    FormItem, FromFormValue, FromParam, FromQuery, FromRequest, Outcome, Query, Request,
+};
 use rocket::response::{self, Responder, Response};
 use rocket::{fairing, fairing::Fairing, Rocket};
 use rocket_contrib::uuid::Uuid as RocketUuid;
 use uuid::Uuid;
 
-use db::{SqlEvent, SqlLocation, SqlOccurrence};
+use db::{
+    SqlEvent, SqlEventTranslation, SqlInvitation, SqlLocation, SqlOccurrence, SqlPage,
+    SqlRecurrence, SqlRevision, SqlTag, SqlUser,
+};
 use diesel::result::QueryResult;
 use diesel::{self, prelude::*};
 use serde::{Deserialize, Serialize};
 
 pub use model::*;
+pub use validation::{Validate, ValidationError, ValidationErrors, MIN_PASSWORD_LENGTH};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 #[serde(transparent)]
@@ -40,6 +56,14 @@ impl<'a, T> FromParam<'a> for Id<T> {
     }
 }
 
+impl<'v, T> FromFormValue<'v> for Id<T> {
+    type Error = <RocketUuid as FromFormValue<'v>>::Error;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Id<T>, Self::Error> {
+        RocketUuid::from_form_value(form_value).map(|uuid| uuid.into_inner().into())
+    }
+}
+
 impl<Item> From<Uuid> for Id<Item> {
     fn from(uuid: Uuid) -> Self {
         Id {
@@ -49,13 +73,73 @@ impl<Item> From<Uuid> for Id<Item> {
     }
 }
 
-pub struct Store(db::Connection);
+impl<Item> fmt::Display for Id<Item> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.id, f)
+    }
+}
+
+/// The user is the logged in one, if any. Changes are recorded as made by them.
+pub struct Store(db::Connection, Option<Id<User>>);
 
 impl Store {
     pub fn fairing() -> StoreFairing {
         StoreFairing
     }
 
+    /// Connections are pooled, so foreign keys are enabled each time one is taken from the pool.
+    fn new(conn: db::Connection) -> Store {
+        db::enable_foreign_keys(&conn).expect("Enabling foreign keys failed.");
+
+        Store(conn, None)
+    }
+
+    /// The author recorded for changes.
+    fn editor(&self) -> Option<db::SqlId<User>> {
+        self.1.clone().map(Into::into)
+    }
+
+    fn new_sql_occurrence(
+        &self,
+        occurrence: OccurrenceWithLocation,
+        event_id: db::SqlId<Event>,
+    ) -> SqlOccurrence {
+        SqlOccurrence {
+            updated_by: self.editor(),
+            ..(occurrence, event_id).into()
+        }
+    }
+
+    /// Retrieves a store outside of a request, e. g. in a fairing.
+    pub fn get_one(rocket: &Rocket) -> Option<Store> {
+        db::Connection::get_one(rocket).map(Store::new)
+    }
+
+    /// Runs `actions` in a transaction that is only committed if `commit` is set.
+    /// This allows to report what the actions would change, without changing anything.
+    pub fn try_out<T, F>(&self, commit: bool, actions: F) -> QueryResult<T>
+    where
+        F: FnOnce() -> QueryResult<T>,
+    {
+        use diesel::result::Error::RollbackTransaction;
+
+        let mut uncommitted = None;
+        let result = self.0.transaction(|| {
+            let value = actions()?;
+            if commit {
+                Ok(value)
+            } else {
+                uncommitted = Some(value);
+                Err(RollbackTransaction)
+            }
+        });
+
+        match (result, uncommitted) {
+            (Err(RollbackTransaction), Some(value)) => Ok(value),
+            (result, _) => result,
+        }
+    }
+
     pub fn read_all(&self, filter: &OccurrenceFilter) -> Overview {
         let locs: HashMap<Id<Location>, Location> = self.all();
         let evts: HashMap<Id<Event>, EventWithOccurrences> =
@@ -71,51 +155,87 @@ impl Store {
         &self,
         filter: &OccurrenceFilter,
     ) -> BTreeMap<NaiveDate, Vec<OccurrenceWithEvent>> {
-        use db::schema::events::dsl::events;
-        use db::schema::occurrences::dsl::{occurrences, start};
+        use db::schema::events::dsl::{deleted_at, events};
 
-        let sql_occurrences = occurrences
-            .filter(apply_occurrence_filter(filter))
-            .order(start.asc())
-            .load::<SqlOccurrence>(&*self.0)
-            .unwrap();
+        let entries = events
+            .filter(deleted_at.is_null())
+            .load::<SqlEvent>(&*self.0)
+            .expect("Loading from database failed.")
+            .into_iter()
+            .flat_map(|sql_event| {
+                self.occurrences_with_event(sql_event, filter)
+                    .expect("Loading from database failed.")
+            });
+
+        group_by_date(entries)
+    }
+
+    /// Like `occurrences_by_date`, but only loads the events that take place at the location.
+    pub fn occurrences_by_date_at(
+        &self,
+        location_id: &Id<Location>,
+        filter: &OccurrenceFilter,
+    ) -> BTreeMap<NaiveDate, Vec<OccurrenceWithEvent>> {
+        use db::schema::events::dsl::{deleted_at, events, id};
+        use db::schema::occurrences::dsl as occurrences;
+        use db::schema::recurrences::dsl as recurrences;
+        use db::SqlId;
 
-        sql_occurrences
+        let raw_location_id: SqlId<Location> = location_id.clone().into();
+        let with_occurrences_here = occurrences::occurrences
+            .select(occurrences::event_id)
+            .filter(occurrences::location_id.eq(raw_location_id.clone()))
+            .filter(apply_occurrence_filter(filter))
+            .into_boxed();
+        let with_recurrences_here = recurrences::recurrences
+            .select(recurrences::event_id)
+            .filter(recurrences::location_id.eq(raw_location_id));
+
+        let entries = events
+            .filter(deleted_at.is_null())
+            .filter(
+                id.eq_any(with_occurrences_here)
+                    .or(id.eq_any(with_recurrences_here)),
+            )
+            .load::<SqlEvent>(&*self.0)
+            .expect("Loading from database failed.")
             .into_iter()
-            .map(|sql_occurrence| {
-                let sql_event = events
-                    .find(sql_occurrence.event_id.clone())
-                    .first::<SqlEvent>(&*self.0)
-                    .unwrap();
-                let (_, occurrence) = sql_occurrence.into();
-                let (_, event) = sql_event.into();
-                OccurrenceWithEvent { occurrence, event }
+            .flat_map(|sql_event| {
+                self.occurrences_with_event(sql_event, filter)
+                    .expect("Loading from database failed.")
             })
-            .fold(
-                BTreeMap::new(),
-                |mut acc: BTreeMap<NaiveDate, Vec<OccurrenceWithEvent>>, entry| {
-                    acc.entry(entry.occurrence.occurrence.start.date())
-                        .and_modify(|entries| entries.push(entry.clone()))
-                        .or_insert_with(|| vec![entry]);
-                    acc
-                },
-            )
+            .filter(|entry| &entry.occurrence.location_id == location_id);
+
+        group_by_date(entries)
     }
 
     pub fn locations_with_occurrences(
         &self,
         filter: &OccurrenceFilter,
     ) -> HashMap<Id<Location>, LocationWithOccurrences> {
-        use db::schema::locations::dsl::locations;
+        use db::schema::locations::dsl::{deleted_at, locations};
+        use db::schema::occurrences::dsl::event_id as occurrence_event_id;
+        use db::schema::recurrences::dsl::{event_id, recurrences as recurrences_table};
+
+        // The occurrences and recurrences of trashed and hidden unpublished events are left out.
+        let recurrences: Vec<(Id<Recurrence>, Recurrence)> = recurrences_table
+            .filter(event_id.eq_any(visible_event_ids(filter)))
+            .load::<SqlRecurrence>(&*self.0)
+            .expect("Loading from database failed.")
+            .into_iter()
+            .map(|sql_recurrence| sql_recurrence.into())
+            .collect();
 
         locations
+            .filter(deleted_at.is_null())
             .load::<SqlLocation>(&*self.0)
             .expect("Loading from database failed.")
             .into_iter()
             .map(|sql_location| {
-                let occurrences: HashMap<Id<Occurrence>, Occurrence> =
+                let mut occurrences: HashMap<Id<Occurrence>, Occurrence> =
                     SqlOccurrence::belonging_to(&sql_location)
                         .filter(apply_occurrence_filter(filter))
+                        .filter(occurrence_event_id.eq_any(visible_event_ids(filter)))
                         .load::<SqlOccurrence>(&*self.0)
                         .expect("Loading from database failed.")
                         .into_iter()
@@ -128,6 +248,16 @@ impl Store {
 
                 let (id, location) = sql_location.into();
 
+                occurrences.extend(
+                    recurrences
+                        .iter()
+                        .filter(|(_, recurrence)| recurrence.first.location_id == id)
+                        .flat_map(|(recurrence_id, recurrence)| {
+                            expand_recurrence(recurrence_id, recurrence, filter)
+                        })
+                        .map(|(occurrence_id, occurrence)| (occurrence_id, occurrence.occurrence)),
+                );
+
                 (
                     id,
                     LocationWithOccurrences {
@@ -150,12 +280,137 @@ pub trait Actions<T> {
     fn delete(&self, id: Self::Id) -> QueryResult<T>;
 }
 
+/// What happens to the occurrences and recurrences at a location when it is deleted.
+pub enum LocationDeletion {
+    /// The location is only deleted if no events take place there.
+    Refuse,
+    /// The occurrences and recurrences are moved to another location.
+    ReassignTo(Id<Location>),
+    /// The occurrences and recurrences are deleted as well.
+    Cascade,
+}
+
+#[derive(Debug)]
+pub enum LocationDeletionError {
+    /// The events that still take place at the location.
+    InUse(HashMap<Id<Event>, Event>),
+    /// The location to reassign to does not exist or is the deleted one.
+    InvalidTarget,
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for LocationDeletionError {
+    fn from(err: diesel::result::Error) -> Self {
+        LocationDeletionError::Database(err)
+    }
+}
+
+impl Store {
+    /// All events with occurrences or recurrences at the location, except trashed ones.
+    pub fn events_at_location(
+        &self,
+        location_id: Id<Location>,
+    ) -> QueryResult<HashMap<Id<Event>, Event>> {
+        use db::schema::events::dsl::{deleted_at, events, id};
+        use db::schema::occurrences::dsl as occurrences;
+        use db::schema::recurrences::dsl as recurrences;
+        use db::SqlId;
+
+        let raw_id: SqlId<Location> = location_id.into();
+        let occurring = occurrences::occurrences
+            .filter(occurrences::location_id.eq(&raw_id))
+            .select(occurrences::event_id);
+        let recurring = recurrences::recurrences
+            .filter(recurrences::location_id.eq(&raw_id))
+            .select(recurrences::event_id);
+
+        events
+            .filter(deleted_at.is_null())
+            .filter(id.eq_any(occurring).or(id.eq_any(recurring)))
+            .load::<SqlEvent>(&*self.0)?
+            .into_iter()
+            .map(|sql_event| self.event_from_sql(sql_event))
+            .collect()
+    }
+
+    /// Deletes the location, handling the occurrences and recurrences there as specified.
+    /// Either all changes are made or none. Trashed events are left as they are, so they can be
+    /// restored with all their occurrences. The location stays in the trash as long as they do.
+    pub fn delete_location(
+        &self,
+        location_id: Id<Location>,
+        deletion: LocationDeletion,
+    ) -> Result<Location, LocationDeletionError> {
+        use db::schema::events::dsl as events;
+        use db::schema::occurrences::dsl as occurrences;
+        use db::schema::recurrences::dsl as recurrences;
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let raw_id: SqlId<Location> = location_id.clone().into();
+            let active_events = || {
+                events::events
+                    .filter(events::deleted_at.is_null())
+                    .select(events::id)
+            };
+            let at_location = occurrences::occurrences
+                .filter(occurrences::location_id.eq(&raw_id))
+                .filter(occurrences::event_id.eq_any(active_events()));
+            let recurring_at_location = recurrences::recurrences
+                .filter(recurrences::location_id.eq(&raw_id))
+                .filter(recurrences::event_id.eq_any(active_events()));
+
+            match deletion {
+                LocationDeletion::Refuse => {
+                    let affected = self.events_at_location(location_id.clone())?;
+                    if !affected.is_empty() {
+                        return Err(LocationDeletionError::InUse(affected));
+                    }
+                }
+                LocationDeletion::ReassignTo(target) => {
+                    if target == location_id || !self.location_exists(target.clone())? {
+                        return Err(LocationDeletionError::InvalidTarget);
+                    }
+                    for event_id in self.events_at_location(location_id.clone())?.keys() {
+                        self.record_event_revision(event_id, RevisionAction::Update)?;
+                    }
+
+                    let raw_target: SqlId<Location> = target.into();
+                    diesel::update(at_location)
+                        .set((
+                            occurrences::location_id.eq(&raw_target),
+                            occurrences::updated_at.eq(db::now()),
+                            occurrences::updated_by.eq(self.editor()),
+                        ))
+                        .execute(&*self.0)?;
+                    diesel::update(recurring_at_location)
+                        .set(recurrences::location_id.eq(&raw_target))
+                        .execute(&*self.0)?;
+                }
+                LocationDeletion::Cascade => {
+                    for event_id in self.events_at_location(location_id.clone())?.keys() {
+                        self.record_event_revision(event_id, RevisionAction::Update)?;
+                    }
+                    diesel::delete(at_location).execute(&*self.0)?;
+                    diesel::delete(recurring_at_location).execute(&*self.0)?;
+                }
+            }
+
+            self.delete(location_id)
+                .map_err(LocationDeletionError::from)
+        })
+    }
+}
+
 use db::schema::locations::dsl::locations as schema;
 impl Actions<Location> for Store {
     type Id = Id<Location>;
 
     fn all(&self) -> HashMap<Self::Id, Location> {
+        use db::schema::locations::dsl::deleted_at;
+
         schema
+            .filter(deleted_at.is_null())
             .load::<SqlLocation>(&*self.0)
             .expect("Could not load database")
             .into_iter()
@@ -164,7 +419,10 @@ impl Actions<Location> for Store {
     }
 
     fn create(&self, item: Location) -> QueryResult<Self::Id> {
-        let sql_item: SqlLocation = item.into();
+        let sql_item = SqlLocation {
+            updated_by: self.editor(),
+            ..item.into()
+        };
         diesel::insert_into(schema)
             .values(&sql_item)
             .execute(&*self.0)?;
@@ -173,74 +431,289 @@ impl Actions<Location> for Store {
     }
 
     fn read(&self, item_id: Self::Id) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
         use db::SqlId;
 
         schema
             .find(SqlId::from(item_id))
+            .filter(deleted_at.is_null())
             .first::<SqlLocation>(&*self.0)
             .map(|x| x.into())
             .map(|(_, x)| x)
     }
 
     fn update(&self, item_id: Self::Id, new_item: Location) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let raw_id: SqlId<Location> = item_id.clone().into();
+            let sql_previous = schema
+                .find(&raw_id)
+                .filter(deleted_at.is_null())
+                .first::<SqlLocation>(&*self.0)?;
+            let created_at = sql_previous.created_at;
+            let (_, previous): (Id<Location>, Location) = sql_previous.into();
+            self.record_revision(
+                db::LOCATION_REVISION,
+                &item_id,
+                RevisionAction::Update,
+                &previous,
+            )?;
+
+            diesel::update(schema.find(&raw_id))
+                .set(SqlLocation {
+                    created_at,
+                    updated_by: self.editor(),
+                    ..new_item.into()
+                })
+                .execute(&*self.0)?;
+
+            Ok(previous)
+        })
+    }
+
+    /// Moves the location to the trash.
+    fn delete(&self, id: Self::Id) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let raw_id: SqlId<Location> = id.clone().into();
+            let (_, previous): (Id<Location>, Location) = schema
+                .find(&raw_id)
+                .filter(deleted_at.is_null())
+                .first::<SqlLocation>(&*self.0)?
+                .into();
+            self.record_revision(
+                db::LOCATION_REVISION,
+                &id,
+                RevisionAction::Delete,
+                &previous,
+            )?;
+
+            diesel::update(schema.find(&raw_id))
+                .set(deleted_at.eq(db::now()))
+                .execute(&*self.0)?;
+
+            Ok(previous)
+        })
+    }
+}
+
+impl Actions<User> for Store {
+    type Id = Id<User>;
+
+    fn all(&self) -> HashMap<Self::Id, User> {
+        use db::schema::users::dsl::users;
+
+        users
+            .load::<SqlUser>(&*self.0)
+            .expect("Could not load database")
+            .into_iter()
+            .map(|x| x.into())
+            .collect()
+    }
+
+    /// The created user can only log in after accepting an invitation.
+    fn create(&self, item: User) -> QueryResult<Self::Id> {
+        use db::schema::users::dsl::users;
+
+        let sql_item: SqlUser = item.into();
+        diesel::insert_into(users)
+            .values(&sql_item)
+            .execute(&*self.0)?;
+
+        Ok(sql_item.id.into())
+    }
+
+    fn read(&self, item_id: Self::Id) -> QueryResult<User> {
+        use db::schema::users::dsl::users;
+        use db::SqlId;
+
+        users
+            .find(SqlId::from(item_id))
+            .first::<SqlUser>(&*self.0)
+            .map(|x| x.into())
+            .map(|(_, x)| x)
+    }
+
+    fn update(&self, item_id: Self::Id, new_item: User) -> QueryResult<User> {
+        use db::schema::users::dsl::{name, users};
         use db::SqlId;
 
-        let raw_id: SqlId<Location> = item_id.into();
-        let (_, previous): (Id<Location>, Location) =
-            schema.find(&raw_id).first::<SqlLocation>(&*self.0)?.into();
+        let raw_id: SqlId<User> = item_id.into();
+        let (_, previous): (Id<User>, User) =
+            users.find(&raw_id).first::<SqlUser>(&*self.0)?.into();
 
-        diesel::update(schema.find(&raw_id))
-            .set::<SqlLocation>(new_item.into())
+        diesel::update(users.find(&raw_id))
+            .set(name.eq(new_item.name))
             .execute(&*self.0)?;
 
         Ok(previous)
     }
 
-    fn delete(&self, id: Self::Id) -> QueryResult<Location> {
+    fn delete(&self, id: Self::Id) -> QueryResult<User> {
+        self.0.transaction(|| {
+            use db::schema::users::dsl::users;
+            use db::SqlId;
+
+            let raw_id: SqlId<User> = id.into();
+            let sql_previous = users.find(&raw_id).first::<SqlUser>(&*self.0)?;
+
+            diesel::delete(SqlInvitation::belonging_to(&sql_previous)).execute(&*self.0)?;
+            diesel::delete(&sql_previous).execute(&*self.0)?;
+
+            let (_, previous) = sql_previous.into();
+            Ok(previous)
+        })
+    }
+}
+
+impl Actions<Page> for Store {
+    type Id = Id<Page>;
+
+    fn all(&self) -> HashMap<Self::Id, Page> {
+        use db::schema::pages::dsl::pages;
+
+        pages
+            .load::<SqlPage>(&*self.0)
+            .expect("Loading from database failed.")
+            .into_iter()
+            .map(|x| x.into())
+            .collect()
+    }
+
+    fn create(&self, item: Page) -> QueryResult<Self::Id> {
+        use db::schema::pages::dsl::pages;
+
+        let sql_item = SqlPage {
+            updated_by: self.editor(),
+            ..item.into()
+        };
+        diesel::insert_into(pages)
+            .values(&sql_item)
+            .execute(&*self.0)?;
+
+        Ok(sql_item.id.into())
+    }
+
+    fn read(&self, item_id: Self::Id) -> QueryResult<Page> {
+        use db::schema::pages::dsl::pages;
         use db::SqlId;
-        let raw_id: SqlId<Location> = id.into();
-        let (_, previous): (Id<Location>, Location) =
-            schema.find(&raw_id).first::<SqlLocation>(&*self.0)?.into();
 
-        diesel::delete(schema.find(&raw_id)).execute(&*self.0)?;
+        pages
+            .find(SqlId::from(item_id))
+            .first::<SqlPage>(&*self.0)
+            .map(|x| x.into())
+            .map(|(_, x)| x)
+    }
+
+    fn update(&self, item_id: Self::Id, new_item: Page) -> QueryResult<Page> {
+        use db::schema::pages::dsl::pages;
+        use db::SqlId;
+
+        let raw_id: SqlId<Page> = item_id.into();
+        let sql_previous = pages.find(&raw_id).first::<SqlPage>(&*self.0)?;
+
+        diesel::update(pages.find(&raw_id))
+            .set(SqlPage {
+                created_at: sql_previous.created_at,
+                updated_by: self.editor(),
+                ..new_item.into()
+            })
+            .execute(&*self.0)?;
+
+        let (_, previous) = sql_previous.into();
+        Ok(previous)
+    }
+
+    fn delete(&self, id: Self::Id) -> QueryResult<Page> {
+        use db::schema::pages::dsl::pages;
+        use db::SqlId;
+
+        let raw_id: SqlId<Page> = id.into();
+        let (_, previous): (Id<Page>, Page) =
+            pages.find(&raw_id).first::<SqlPage>(&*self.0)?.into();
+
+        diesel::delete(pages.find(&raw_id)).execute(&*self.0)?;
 
         Ok(previous)
     }
 }
 
-#[derive(Debug)]
+impl Store {
+    pub fn published_page(&self, page_slug: &str) -> QueryResult<Option<Page>> {
+        use db::schema::pages::dsl::{pages, published, slug};
+
+        Ok(pages
+            .filter(slug.eq(page_slug))
+            .filter(published.eq(true))
+            .first::<SqlPage>(&*self.0)
+            .optional()?
+            .map(|sql_page| {
+                let (_, page) = sql_page.into();
+                page
+            }))
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct OccurrenceFilter {
     pub before: Option<NaiveDateTime>,
     pub after: Option<NaiveDateTime>,
+    /// Only occurrences of events with this tag.
+    pub tag: Option<String>,
+    /// Only occurrences of events in this category.
+    pub category: Option<Category>,
+    /// Also occurrences of drafts and of events scheduled for later, which only admins see.
+    /// It is never taken from the query.
+    pub unpublished: bool,
 }
 
-impl Default for OccurrenceFilter {
-    fn default() -> Self {
-        OccurrenceFilter {
-            before: None,
-            after: None,
-        }
+impl OccurrenceFilter {
+    /// Whether an occurrence starting at `start` passes the filter, like in `apply_occurrence_filter`.
+    pub fn matches(&self, start: &NaiveDateTime) -> bool {
+        self.before.map_or(true, |before| *start < before)
+            && self.after.map_or(true, |after| *start > after)
+    }
+
+    /// Whether the occurrences of `event` pass the filter's tag, category and publication.
+    pub fn matches_event(&self, event: &Event) -> bool {
+        let tagged = self.tag.as_ref().map_or(true, |tag| {
+            event
+                .tags
+                .iter()
+                .flatten()
+                .any(|event_tag| event_tag == tag)
+        });
+
+        tagged
+            && self
+                .category
+                .map_or(true, |category| event.current_category() == Some(category))
+            && (self.unpublished || event.is_published())
     }
-}
 
-impl OccurrenceFilter {
     pub fn upcoming() -> Self {
         let today = NaiveDateTime::new(
             chrono::Local::today().naive_local(),
             chrono::NaiveTime::from_hms(0, 0, 0),
         );
         OccurrenceFilter {
-            before: None,
             after: Some(today),
+            ..OccurrenceFilter::default()
         }
     }
 }
 
 #[derive(Debug, Serialize)]
+#[allow(clippy::enum_variant_names)] // The names are part of the API's error responses.
 pub enum OccurrenceFilterError {
     InvalidBeforeDate,
     InvalidAfterDate,
     InvalidRange,
+    InvalidCategory,
 }
 
 impl<'r> Responder<'r> for OccurrenceFilterError {
@@ -263,15 +736,30 @@ impl<'q> FromQuery<'q> for OccurrenceFilter {
             .map(|item| decode_datetime(item).ok_or(InvalidBeforeDate))
             .transpose()?;
         let after: Option<NaiveDateTime> = query
+            .clone()
             .find(|i| i.key == "after")
             .map(|item| decode_datetime(item).ok_or(InvalidAfterDate))
             .transpose()?;
+        let tag: Option<String> = query
+            .clone()
+            .find(|i| i.key == "tag")
+            .map(|item| item.value.url_decode_lossy());
+        let category: Option<Category> = query
+            .find(|i| i.key == "category")
+            .map(|item| Category::from_form_value(item.value).map_err(|_| InvalidCategory))
+            .transpose()?;
 
         if after < before {
             return Err(InvalidRange)?;
         }
 
-        Ok(OccurrenceFilter { before, after })
+        Ok(OccurrenceFilter {
+            before,
+            after,
+            tag,
+            category,
+            unpublished: false,
+        })
     }
 }
 
@@ -284,54 +772,149 @@ impl Store {
         &self,
         filter: &OccurrenceFilter,
     ) -> HashMap<Id<Event>, EventWithOccurrences> {
-        use db::schema::events::dsl::events;
+        use db::schema::events::dsl::{deleted_at, events};
 
         events
+            .filter(deleted_at.is_null())
             .load::<SqlEvent>(&*self.0)
             .expect("Loading from database failed.")
             .into_iter()
-            .map(|sql_event| {
-                let occurrences: Vec<OccurrenceWithLocation> =
-                    SqlOccurrence::belonging_to(&sql_event)
-                        .filter(apply_occurrence_filter(filter))
-                        .load::<SqlOccurrence>(&*self.0)
-                        .expect("Loading from database failed.")
-                        .into_iter()
-                        .map(|sql_occurrence| {
-                            let (_, occurrence) = sql_occurrence.into();
+            .filter_map(|sql_event| {
+                let occurrences: Vec<OccurrenceWithLocation> = self
+                    .occurrences_of_event(&sql_event, filter)
+                    .expect("Loading from database failed.")
+                    .into_iter()
+                    .map(|(_, occurrence)| occurrence)
+                    .collect();
+                let recurrences: Vec<Recurrence> = self
+                    .recurrences_of_event(&sql_event)
+                    .expect("Loading from database failed.")
+                    .into_iter()
+                    .map(|(_, recurrence)| recurrence)
+                    .collect();
+                let translations = self
+                    .translations_of_event(&sql_event)
+                    .expect("Loading from database failed.");
+
+                let (id, event) = self
+                    .event_from_sql(sql_event)
+                    .expect("Loading from database failed.");
+                if !filter.matches_event(&event) {
+                    return None;
+                }
+
+                Some((
+                    id,
+                    EventWithOccurrences {
+                        event,
+                        occurrences,
+                        recurrences: Some(recurrences),
+                        translations: Some(translations),
+                    },
+                ))
+            })
+            .collect()
+    }
 
-                            occurrence
-                        })
-                        .collect();
+    /// Lists the events ordered by their next occurrence. Events without one come last.
+    pub fn events_by_next_occurrence(
+        &self,
+        filter: &OccurrenceFilter,
+    ) -> Vec<(Id<Event>, EventWithOccurrences)> {
+        let mut events: Vec<(Id<Event>, EventWithOccurrences)> = self
+            .all_events_with_occurrences(filter)
+            .into_iter()
+            .collect();
+        events.sort_by_key(|(_, item)| {
+            let next = item
+                .next_occurrence()
+                .map(|occurrence| occurrence.occurrence.start);
 
-                let (id, event) = sql_event.into();
+            (next.is_none(), next)
+        });
 
-                (id, EventWithOccurrences { event, occurrences })
-            })
-            .collect()
+        events
     }
 
+    /// Occurrences that are generated by one of the recurrence rules are not stored separately.
+    /// The recurrence rules get new ids.
     pub fn create_event_with_occurrences(
         &self,
         item: EventWithOccurrences,
     ) -> QueryResult<Id<Event>> {
-        use db::schema::events::dsl::events;
-        let sql_event: SqlEvent = item.event.into();
-        diesel::insert_into(events)
-            .values(&sql_event)
-            .execute(&*self.0)?;
-
-        use db::schema::occurrences::dsl::occurrences;
-        let sql_occurrences: Vec<SqlOccurrence> = item
+        let occurrences = item
             .occurrences
             .into_iter()
-            .map(|occurrence| (occurrence, sql_event.id.clone()).into())
+            .map(|occurrence| OccurrenceWithLocation {
+                id: None,
+                ..occurrence
+            })
             .collect();
-        diesel::insert_into(occurrences)
-            .values(&sql_occurrences)
-            .execute(&*self.0)?;
+        let recurrences = item.recurrences.map(|recurrences| {
+            recurrences
+                .into_iter()
+                .map(|recurrence| Recurrence {
+                    id: None,
+                    ..recurrence
+                })
+                .collect()
+        });
+
+        self.insert_event_with_occurrences(
+            Uuid::new_v4().into(),
+            EventWithOccurrences {
+                occurrences,
+                recurrences,
+                ..item
+            },
+        )
+    }
 
-        Ok(sql_event.id.into())
+    /// Stores a new event under `event_id`, keeping the ids of its occurrences and recurrence
+    /// rules. This is also used to restore deleted events.
+    fn insert_event_with_occurrences(
+        &self,
+        event_id: Id<Event>,
+        item: EventWithOccurrences,
+    ) -> QueryResult<Id<Event>> {
+        self.0.transaction(|| {
+            use db::schema::events::dsl::events;
+            let sql_event = SqlEvent {
+                id: event_id.into(),
+                updated_by: self.editor(),
+                ..item.event.clone().into()
+            };
+            diesel::insert_into(events)
+                .values(&sql_event)
+                .execute(&*self.0)?;
+
+            let recurrences = item.recurrences.unwrap_or_default();
+
+            use db::schema::occurrences::dsl::occurrences;
+            let sql_occurrences: Vec<SqlOccurrence> = item
+                .occurrences
+                .into_iter()
+                .filter(|occurrence| !is_generated(occurrence, &recurrences))
+                .map(|occurrence| self.new_sql_occurrence(occurrence, sql_event.id.clone()))
+                .collect();
+            diesel::insert_into(occurrences)
+                .values(&sql_occurrences)
+                .execute(&*self.0)?;
+
+            use db::schema::recurrences::dsl::recurrences as recurrences_table;
+            let sql_recurrences: Vec<SqlRecurrence> = recurrences
+                .into_iter()
+                .map(|recurrence| (recurrence, sql_event.id.clone()).into())
+                .collect();
+            diesel::insert_into(recurrences_table)
+                .values(&sql_recurrences)
+                .execute(&*self.0)?;
+
+            self.insert_translations(&sql_event.id, item.translations.unwrap_or_default())?;
+            self.insert_tags(&sql_event.id, item.event.tags.unwrap_or_default())?;
+
+            Ok(sql_event.id.into())
+        })
     }
 
     pub fn read_event_with_occurrences(
@@ -339,90 +922,318 @@ impl Store {
         item_id: Id<Event>,
         filter: &OccurrenceFilter,
     ) -> QueryResult<EventWithOccurrences> {
-        use db::schema::events::dsl::events;
+        use db::schema::events::dsl::{deleted_at, events};
         use db::SqlId;
         let sql_event = events
             .find(SqlId::from(item_id))
+            .filter(deleted_at.is_null())
             .first::<SqlEvent>(&*self.0)?;
 
-        let occurrences: Vec<OccurrenceWithLocation> = SqlOccurrence::belonging_to(&sql_event)
-            .filter(apply_occurrence_filter(filter))
-            .load::<SqlOccurrence>(&*self.0)?
+        let occurrences: Vec<OccurrenceWithLocation> = self
+            .occurrences_of_event(&sql_event, filter)?
             .into_iter()
-            .map(|sql_occurrence| {
-                let (_, occurrence) = sql_occurrence.into();
-
-                occurrence
-            })
+            .map(|(_, occurrence)| occurrence)
+            .collect();
+        let recurrences: Vec<Recurrence> = self
+            .recurrences_of_event(&sql_event)?
+            .into_iter()
+            .map(|(_, recurrence)| recurrence)
             .collect();
+        let translations = self.translations_of_event(&sql_event)?;
 
-        let (_, event) = sql_event.into();
+        let (_, event) = self.event_from_sql(sql_event)?;
 
-        Ok(EventWithOccurrences { event, occurrences })
+        Ok(EventWithOccurrences {
+            event,
+            occurrences,
+            recurrences: Some(recurrences),
+            translations: Some(translations),
+        })
     }
 
+    /// Replaces the stored occurrences matching `filter`. Occurrences and recurrence rules are
+    /// matched by their id, so unchanged ones keep it. The recurrence rules are only replaced if
+    /// `new_item` contains some. The returned previous event only lists the stored occurrences.
     pub fn update_event_with_occurrences(
         &self,
         item_id: Id<Event>,
         new_item: EventWithOccurrences,
         filter: &OccurrenceFilter,
     ) -> QueryResult<EventWithOccurrences> {
-        use db::SqlId;
-
-        let raw_id: SqlId<Event> = item_id.into();
-        use db::schema::events::dsl::events;
-        let sql_previous = events.find(raw_id.clone()).first::<SqlEvent>(&*self.0)?;
+        self.replace_event_with_occurrences(item_id, new_item, filter, false)
+    }
 
-        let associated_occurrences = SqlOccurrence::belonging_to(&sql_previous);
-        let previous_occurrences: Vec<OccurrenceWithLocation> = associated_occurrences
-            .filter(apply_occurrence_filter(filter))
-            .load::<SqlOccurrence>(&*self.0)?
-            .into_iter()
-            .map(|sql_occurrence| {
-                let (_, occurrence) = sql_occurrence.into();
+    /// Like `update_event_with_occurrences`. Ids that match nothing stored are replaced by new
+    /// ones, unless `keep_unknown_ids` is set. That is only safe for ids the event had before,
+    /// e. g. when restoring a revision.
+    fn replace_event_with_occurrences(
+        &self,
+        item_id: Id<Event>,
+        new_item: EventWithOccurrences,
+        filter: &OccurrenceFilter,
+        keep_unknown_ids: bool,
+    ) -> QueryResult<EventWithOccurrences> {
+        self.0.transaction(|| {
+            use db::SqlId;
+
+            self.record_event_revision(&item_id, RevisionAction::Update)?;
+
+            let raw_id: SqlId<Event> = item_id.into();
+            use db::schema::events::dsl::{deleted_at, events};
+            let sql_previous = events
+                .find(raw_id.clone())
+                .filter(deleted_at.is_null())
+                .first::<SqlEvent>(&*self.0)?;
+
+            let previous_occurrences: Vec<OccurrenceWithLocation> =
+                SqlOccurrence::belonging_to(&sql_previous)
+                    .filter(apply_occurrence_filter(filter))
+                    .load::<SqlOccurrence>(&*self.0)?
+                    .into_iter()
+                    .map(|sql_occurrence| {
+                        let (_, occurrence) = sql_occurrence.into();
+
+                        occurrence
+                    })
+                    .collect();
+            let previous_recurrences: Vec<Recurrence> = self
+                .recurrences_of_event(&sql_previous)?
+                .into_iter()
+                .map(|(_, recurrence)| recurrence)
+                .collect();
+            let previous_translations = self.translations_of_event(&sql_previous)?;
+            let (_, previous) = self.event_from_sql(sql_previous.clone())?;
+
+            if let Some(new_tags) = new_item.event.tags.clone() {
+                diesel::delete(SqlTag::belonging_to(&sql_previous)).execute(&*self.0)?;
+                self.insert_tags(&raw_id, new_tags)?;
+            }
+            if let Some(new_translations) = new_item.translations {
+                diesel::delete(SqlEventTranslation::belonging_to(&sql_previous))
+                    .execute(&*self.0)?;
+                self.insert_translations(&raw_id, new_translations)?;
+            }
+
+            let keeps_publication = new_item.event.publication.is_none();
+            let keeps_price = new_item.event.price.is_none();
+            let keeps_category = new_item.event.category.is_none();
+            let mut new_sql_item = SqlEvent {
+                created_at: sql_previous.created_at,
+                updated_by: self.editor(),
+                preview_token: sql_previous.preview_token.clone(),
+                ..new_item.event.into()
+            };
+            // Events stay new since they first became public, not since they were last saved.
+            let was_public =
+                Publication::from_code(&sql_previous.publication, sql_previous.publish_at)
+                    .map_or(false, |publication| publication.is_public(db::now()));
+            if keeps_publication {
+                new_sql_item.publication = sql_previous.publication.clone();
+                new_sql_item.publish_at = sql_previous.publish_at;
+                new_sql_item.published_at = sql_previous.published_at;
+            } else if was_public && new_sql_item.publication == Publication::Published.code() {
+                new_sql_item.published_at = sql_previous.published_at;
+            }
+            if keeps_price {
+                new_sql_item.price = sql_previous.price.clone();
+            }
+            if keeps_category {
+                new_sql_item.category = sql_previous.category.clone();
+            }
+            diesel::update(&sql_previous)
+                .set(new_sql_item)
+                .execute(&*self.0)?;
+
+            let recurrences = match new_item.recurrences {
+                Some(new_recurrences) => {
+                    use db::schema::recurrences::dsl::recurrences as recurrences_table;
+
+                    // Rules without an id keep the one of an identical previous rule.
+                    let mut unmatched = previous_recurrences.clone();
+                    let new_recurrences: Vec<Recurrence> = new_recurrences
+                        .into_iter()
+                        .map(|recurrence| {
+                            let matching =
+                                unmatched.iter().position(|previous| match recurrence.id {
+                                    Some(ref recurrence_id) => {
+                                        previous.id.as_ref() == Some(recurrence_id)
+                                    }
+                                    None => previous.same_as(&recurrence),
+                                });
+                            let id = match matching {
+                                Some(index) => unmatched.remove(index).id,
+                                None if keep_unknown_ids => recurrence.id.clone(),
+                                None => None,
+                            };
+
+                            Recurrence {
+                                id: Some(id.unwrap_or_else(|| Uuid::new_v4().into())),
+                                ..recurrence
+                            }
+                        })
+                        .collect();
 
-                occurrence
+                    diesel::delete(SqlRecurrence::belonging_to(&sql_previous)).execute(&*self.0)?;
+                    let sql_recurrences: Vec<SqlRecurrence> = new_recurrences
+                        .iter()
+                        .map(|recurrence| (recurrence.clone(), raw_id.clone()).into())
+                        .collect();
+                    diesel::insert_into(recurrences_table)
+                        .values(&sql_recurrences)
+                        .execute(&*self.0)?;
+
+                    new_recurrences
+                }
+                None => previous_recurrences.clone(),
+            };
+
+            // Generated occurrences with their own status or overrides are stored separately under
+            // the same id, like in `update_occurrence`.
+            let mut recurrences = recurrences;
+            let mut new_occurrences = new_item.occurrences;
+            let mut taken_ids = Vec::new();
+            for occurrence in &mut new_occurrences {
+                if occurrence.current_status() == OccurrenceStatus::default()
+                    && occurrence.current_overrides() == OccurrenceOverrides::default()
+                {
+                    continue;
+                }
+                if let Some(recurrence) = recurrences
+                    .iter_mut()
+                    .find(|recurrence| recurrence.generates(occurrence))
+                {
+                    use db::schema::recurrences::dsl as recurrences;
+
+                    let date = occurrence.occurrence.start.date();
+                    let recurrence_id = recurrence.id.clone().expect("Stored rules have an id.");
+                    recurrence.exceptions.push(date);
+                    diesel::update(
+                        recurrences::recurrences.find(SqlId::from(recurrence_id.clone())),
+                    )
+                    .set(
+                        recurrences::exceptions
+                            .eq(serde_json::to_string(&recurrence.exceptions).unwrap()),
+                    )
+                    .execute(&*self.0)?;
+
+                    let occurrence_id = generated_id(&recurrence_id, date);
+                    occurrence.id = Some(occurrence_id.clone());
+                    taken_ids.push(occurrence_id);
+                }
+            }
+
+            use db::schema::occurrences::dsl as occurrences;
+            let mut unmatched = previous_occurrences.clone();
+            for occurrence in new_occurrences
+                .into_iter()
+                .filter(|occurrence| !is_generated(occurrence, &recurrences))
+            {
+                // Occurrences without an id keep the one of an identical previous occurrence.
+                let matching = unmatched.iter().position(|previous| match occurrence.id {
+                    Some(ref occurrence_id) => previous.id.as_ref() == Some(occurrence_id),
+                    None => previous.same_as(&occurrence),
+                });
+
+                match matching {
+                    Some(index) => {
+                        let previous = unmatched.remove(index);
+                        let status = occurrence
+                            .status
+                            .clone()
+                            .unwrap_or_else(|| previous.current_status());
+                        let overrides = occurrence
+                            .overrides
+                            .clone()
+                            .unwrap_or_else(|| previous.current_overrides());
+                        if !previous.same_as(&occurrence)
+                            || previous.current_status() != status
+                            || previous.current_overrides() != overrides
+                        {
+                            let raw_occurrence_id: SqlId<Occurrence> =
+                                previous.id.expect("Stored occurrences have an id.").into();
+                            diesel::update(occurrences::occurrences.find(raw_occurrence_id))
+                                .set((
+                                    occurrences::start.eq(occurrence.occurrence.start),
+                                    occurrences::duration.eq(occurrence.occurrence.duration as i32),
+                                    occurrences::location_id
+                                        .eq(SqlId::from(occurrence.location_id)),
+                                    occurrences::status.eq(status.state.code()),
+                                    occurrences::status_note.eq(status.note),
+                                    occurrences::title_suffix.eq(overrides.title_suffix),
+                                    occurrences::teaser_override.eq(overrides.teaser),
+                                    occurrences::note.eq(overrides.note),
+                                    occurrences::updated_at.eq(db::now()),
+                                    occurrences::updated_by.eq(self.editor()),
+                                ))
+                                .execute(&*self.0)?;
+                        }
+                    }
+                    None => {
+                        // Unknown ids are only kept when restoring, otherwise they could collide.
+                        let id = occurrence
+                            .id
+                            .clone()
+                            .filter(|id| keep_unknown_ids || taken_ids.contains(id));
+                        let sql_occurrence = self.new_sql_occurrence(
+                            OccurrenceWithLocation { id, ..occurrence },
+                            raw_id.clone(),
+                        );
+                        diesel::insert_into(occurrences::occurrences)
+                            .values(&sql_occurrence)
+                            .execute(&*self.0)?;
+                    }
+                }
+            }
+            for previous in unmatched {
+                let raw_occurrence_id: SqlId<Occurrence> =
+                    previous.id.expect("Stored occurrences have an id.").into();
+                diesel::delete(occurrences::occurrences.find(raw_occurrence_id))
+                    .execute(&*self.0)?;
+            }
+
+            Ok(EventWithOccurrences {
+                event: previous,
+                occurrences: previous_occurrences,
+                recurrences: Some(previous_recurrences),
+                translations: Some(previous_translations),
             })
-            .collect();
-
-        diesel::delete(associated_occurrences.filter(apply_occurrence_filter(&filter)))
-            .execute(&*self.0)?;
-
-        let new_sql_item: SqlEvent = new_item.event.into();
-        diesel::update(&sql_previous)
-            .set(new_sql_item)
-            .execute(&*self.0)?;
-
-        use db::schema::occurrences::dsl::occurrences as occurrences_table;
-        let sql_occurrences: Vec<SqlOccurrence> = new_item
-            .occurrences
-            .into_iter()
-            .map(|occurrence| (occurrence, raw_id.clone()).into())
-            .collect();
-        diesel::insert_into(occurrences_table)
-            .values(&sql_occurrences)
-            .execute(&*self.0)?;
-
-        let (_, previous) = sql_previous.into();
-        Ok(EventWithOccurrences {
-            event: previous,
-            occurrences: previous_occurrences,
         })
     }
 
+    /// Moves the event to the trash. Its occurrences are kept, so it can be restored as it was.
+    /// The returned event only lists the stored occurrences.
     pub fn delete_event_with_occurrences(
         &self,
         id: Id<Event>,
     ) -> QueryResult<EventWithOccurrences> {
-        use db::SqlId;
+        self.0.transaction(|| {
+            use db::schema::events::dsl::{deleted_at, events};
+            use db::SqlId;
 
-        let raw_id: SqlId<Event> = id.into();
-        use db::schema::events::dsl::events;
-        let sql_previous = events.find(raw_id).first::<SqlEvent>(&*self.0)?;
+            self.record_event_revision(&id, RevisionAction::Delete)?;
 
-        let occurrences: Vec<OccurrenceWithLocation> = SqlOccurrence::belonging_to(&sql_previous)
-            .load::<SqlOccurrence>(&*self.0)
-            .expect("Loading from database failed.")
+            let raw_id: SqlId<Event> = id.into();
+            let sql_previous = events
+                .find(raw_id)
+                .filter(deleted_at.is_null())
+                .first::<SqlEvent>(&*self.0)?;
+            let previous = self.stored_event_with_occurrences(&sql_previous)?;
+
+            diesel::update(&sql_previous)
+                .set(deleted_at.eq(db::now()))
+                .execute(&*self.0)?;
+
+            Ok(previous)
+        })
+    }
+
+    /// The event with its stored occurrences, leaving out those generated by recurrence rules.
+    fn stored_event_with_occurrences(
+        &self,
+        sql_event: &SqlEvent,
+    ) -> QueryResult<EventWithOccurrences> {
+        let occurrences: Vec<OccurrenceWithLocation> = SqlOccurrence::belonging_to(sql_event)
+            .load::<SqlOccurrence>(&*self.0)?
             .into_iter()
             .map(|sql_occurrence| {
                 let (_, occurrence) = sql_occurrence.into();
@@ -430,18 +1241,1058 @@ impl Store {
                 occurrence
             })
             .collect();
+        let recurrences: Vec<Recurrence> = self
+            .recurrences_of_event(sql_event)?
+            .into_iter()
+            .map(|(_, recurrence)| recurrence)
+            .collect();
+        let translations = self.translations_of_event(sql_event)?;
+        let (_, event) = self.event_from_sql(sql_event.clone())?;
 
-        diesel::delete(SqlOccurrence::belonging_to(&sql_previous)).execute(&*self.0)?;
-
-        diesel::delete(&sql_previous).execute(&*self.0)?;
-
-        let (_, previous) = sql_previous.into();
         Ok(EventWithOccurrences {
-            event: previous,
+            event,
             occurrences,
+            recurrences: Some(recurrences),
+            translations: Some(translations),
         })
     }
-}
+
+    pub fn find_event_by_title(&self, event_title: &str) -> QueryResult<Option<Id<Event>>> {
+        use db::schema::events::dsl::{deleted_at, events, title};
+
+        Ok(events
+            .filter(deleted_at.is_null())
+            .filter(title.eq(event_title))
+            .first::<SqlEvent>(&*self.0)
+            .optional()?
+            .map(|sql_event| sql_event.id.into()))
+    }
+
+    /// Adds occurrences and recurrence rules to an event, keeping its existing ones.
+    pub fn add_occurrences(
+        &self,
+        event_id: Id<Event>,
+        new_occurrences: Vec<OccurrenceWithLocation>,
+        new_recurrences: Vec<Recurrence>,
+    ) -> QueryResult<()> {
+        self.0.transaction(|| {
+            use db::schema::occurrences::dsl::occurrences;
+            use db::schema::recurrences::dsl::recurrences;
+            use db::SqlId;
+
+            self.record_event_revision(&event_id, RevisionAction::Update)?;
+
+            let raw_id: SqlId<Event> = event_id.into();
+
+            let sql_occurrences: Vec<SqlOccurrence> = new_occurrences
+                .into_iter()
+                .map(|occurrence| self.new_sql_occurrence(occurrence, raw_id.clone()))
+                .collect();
+            diesel::insert_into(occurrences)
+                .values(&sql_occurrences)
+                .execute(&*self.0)?;
+
+            let sql_recurrences: Vec<SqlRecurrence> = new_recurrences
+                .into_iter()
+                .map(|recurrence| (recurrence, raw_id.clone()).into())
+                .collect();
+            diesel::insert_into(recurrences)
+                .values(&sql_recurrences)
+                .execute(&*self.0)?;
+
+            Ok(())
+        })
+    }
+
+    /// Lists the occurrences of a single event that match `filter`, ordered by their start.
+    pub fn event_occurrences(
+        &self,
+        event_id: Id<Event>,
+        filter: &OccurrenceFilter,
+    ) -> QueryResult<Vec<OccurrenceWithEvent>> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::SqlId;
+
+        let sql_event = events
+            .find(SqlId::from(event_id))
+            .filter(deleted_at.is_null())
+            .first::<SqlEvent>(&*self.0)?;
+
+        self.occurrences_with_event(sql_event, filter)
+    }
+
+    /// Looks up one of the event's occurrences, either stored or generated by a recurrence rule.
+    pub fn read_occurrence(
+        &self,
+        event_id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+    ) -> QueryResult<OccurrenceWithLocation> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::SqlId;
+
+        let sql_event = events
+            .find(SqlId::from(event_id))
+            .filter(deleted_at.is_null())
+            .first::<SqlEvent>(&*self.0)?;
+
+        if let Some(sql_occurrence) = self.stored_occurrence(&sql_event, &occurrence_id)? {
+            let (_, occurrence) = sql_occurrence.into();
+            return Ok(occurrence);
+        }
+
+        self.generated_occurrence(&sql_event, &occurrence_id)?
+            .map(|(_, _, occurrence)| occurrence)
+            .ok_or(diesel::result::Error::NotFound)
+    }
+
+    /// Changes a single occurrence, keeping its id. An occurrence generated by a recurrence rule
+    /// is stored separately from then on and the rule skips its date.
+    pub fn update_occurrence(
+        &self,
+        event_id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+        new_occurrence: OccurrenceWithLocation,
+    ) -> QueryResult<OccurrenceWithLocation> {
+        use db::schema::occurrences::dsl::occurrences;
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            self.record_event_revision(&event_id, RevisionAction::Update)?;
+
+            // A stored occurrence keeps its creation time, a generated one is new.
+            let created_at: Option<NaiveDateTime> = occurrences
+                .find(SqlId::from(occurrence_id.clone()))
+                .first::<SqlOccurrence>(&*self.0)
+                .optional()?
+                .map(|sql_occurrence| sql_occurrence.created_at);
+            let (sql_event, previous) = self.take_occurrence(event_id, &occurrence_id)?;
+
+            let sql_occurrence = self.new_sql_occurrence(
+                OccurrenceWithLocation {
+                    id: Some(occurrence_id),
+                    status: new_occurrence
+                        .status
+                        .clone()
+                        .or_else(|| previous.status.clone()),
+                    overrides: new_occurrence
+                        .overrides
+                        .clone()
+                        .or_else(|| previous.overrides.clone()),
+                    ..new_occurrence
+                },
+                sql_event.id,
+            );
+            let sql_occurrence = SqlOccurrence {
+                created_at: created_at.unwrap_or(sql_occurrence.created_at),
+                ..sql_occurrence
+            };
+            diesel::insert_into(occurrences)
+                .values(&sql_occurrence)
+                .execute(&*self.0)?;
+
+            Ok(previous)
+        })
+    }
+
+    /// Marks a single occurrence as e. g. cancelled, keeping everything else. Returns the
+    /// previous status.
+    pub fn set_occurrence_status(
+        &self,
+        event_id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+        status: OccurrenceStatus,
+    ) -> QueryResult<OccurrenceStatus> {
+        self.0.transaction(|| {
+            let previous = self.read_occurrence(event_id.clone(), occurrence_id.clone())?;
+            self.update_occurrence(
+                event_id,
+                occurrence_id,
+                OccurrenceWithLocation {
+                    status: Some(status),
+                    ..previous.clone()
+                },
+            )?;
+
+            Ok(previous.current_status())
+        })
+    }
+
+    /// Removes a single occurrence. For an occurrence generated by a recurrence rule,
+    /// the rule skips its date from then on.
+    pub fn delete_occurrence(
+        &self,
+        event_id: Id<Event>,
+        occurrence_id: Id<Occurrence>,
+    ) -> QueryResult<OccurrenceWithLocation> {
+        self.0.transaction(|| {
+            self.record_event_revision(&event_id, RevisionAction::Update)?;
+
+            self.take_occurrence(event_id, &occurrence_id)
+                .map(|(_, previous)| previous)
+        })
+    }
+
+    /// Removes the occurrence from the event, either by deleting it or by excluding its date from
+    /// the recurrence rule generating it. Returns the event and the removed occurrence.
+    fn take_occurrence(
+        &self,
+        event_id: Id<Event>,
+        occurrence_id: &Id<Occurrence>,
+    ) -> QueryResult<(SqlEvent, OccurrenceWithLocation)> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::schema::recurrences::dsl::{exceptions, recurrences};
+        use db::SqlId;
+
+        let sql_event = events
+            .find(SqlId::from(event_id))
+            .filter(deleted_at.is_null())
+            .first::<SqlEvent>(&*self.0)?;
+
+        if let Some(sql_occurrence) = self.stored_occurrence(&sql_event, occurrence_id)? {
+            diesel::delete(&sql_occurrence).execute(&*self.0)?;
+
+            let (_, previous) = sql_occurrence.into();
+            return Ok((sql_event, previous));
+        }
+
+        match self.generated_occurrence(&sql_event, occurrence_id)? {
+            Some((recurrence_id, recurrence, previous)) => {
+                let mut new_exceptions = recurrence.exceptions;
+                new_exceptions.push(previous.occurrence.start.date());
+                diesel::update(recurrences.find(SqlId::from(recurrence_id)))
+                    .set(exceptions.eq(serde_json::to_string(&new_exceptions).unwrap()))
+                    .execute(&*self.0)?;
+
+                Ok((sql_event, previous))
+            }
+            None => Err(diesel::result::Error::NotFound),
+        }
+    }
+
+    fn stored_occurrence(
+        &self,
+        sql_event: &SqlEvent,
+        occurrence_id: &Id<Occurrence>,
+    ) -> QueryResult<Option<SqlOccurrence>> {
+        use db::schema::occurrences::dsl::id;
+        use db::SqlId;
+
+        SqlOccurrence::belonging_to(sql_event)
+            .filter(id.eq(SqlId::from(occurrence_id.clone())))
+            .first::<SqlOccurrence>(&*self.0)
+            .optional()
+    }
+
+    /// Finds the recurrence rule generating the occurrence.
+    fn generated_occurrence(
+        &self,
+        sql_event: &SqlEvent,
+        occurrence_id: &Id<Occurrence>,
+    ) -> QueryResult<Option<(Id<Recurrence>, Recurrence, OccurrenceWithLocation)>> {
+        for (recurrence_id, recurrence) in self.recurrences_of_event(sql_event)? {
+            let generated =
+                expand_recurrence(&recurrence_id, &recurrence, &OccurrenceFilter::default())
+                    .into_iter()
+                    .find(|(generated_id, _)| generated_id == occurrence_id);
+
+            if let Some((_, occurrence)) = generated {
+                return Ok(Some((recurrence_id, recurrence, occurrence)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn occurrences_with_event(
+        &self,
+        sql_event: SqlEvent,
+        filter: &OccurrenceFilter,
+    ) -> QueryResult<Vec<OccurrenceWithEvent>> {
+        let occurrences = self.occurrences_of_event(&sql_event, filter)?;
+        let (event_id, event): (Id<Event>, Event) = self.event_from_sql(sql_event)?;
+        if !filter.matches_event(&event) {
+            return Ok(vec![]);
+        }
+
+        Ok(occurrences
+            .into_iter()
+            .map(|(id, occurrence)| OccurrenceWithEvent {
+                id,
+                occurrence,
+                event_id: event_id.clone(),
+                event: event.clone(),
+            })
+            .collect())
+    }
+
+    /// Lists the event's occurrences matching `filter`, including those generated by its
+    /// recurrence rules, ordered by their start.
+    fn occurrences_of_event(
+        &self,
+        sql_event: &SqlEvent,
+        filter: &OccurrenceFilter,
+    ) -> QueryResult<Vec<(Id<Occurrence>, OccurrenceWithLocation)>> {
+        let mut occurrences: Vec<(Id<Occurrence>, OccurrenceWithLocation)> =
+            SqlOccurrence::belonging_to(sql_event)
+                .filter(apply_occurrence_filter(filter))
+                .load::<SqlOccurrence>(&*self.0)?
+                .into_iter()
+                .map(|sql_occurrence| sql_occurrence.into())
+                .collect();
+
+        for (id, recurrence) in self.recurrences_of_event(sql_event)? {
+            occurrences.extend(expand_recurrence(&id, &recurrence, filter));
+        }
+        occurrences.sort_by_key(|(_, occurrence)| occurrence.occurrence.start);
+
+        Ok(occurrences)
+    }
+
+    fn recurrences_of_event(
+        &self,
+        sql_event: &SqlEvent,
+    ) -> QueryResult<Vec<(Id<Recurrence>, Recurrence)>> {
+        Ok(SqlRecurrence::belonging_to(sql_event)
+            .load::<SqlRecurrence>(&*self.0)?
+            .into_iter()
+            .map(|sql_recurrence| sql_recurrence.into())
+            .collect())
+    }
+
+    fn translations_of_event(
+        &self,
+        sql_event: &SqlEvent,
+    ) -> QueryResult<HashMap<Locale, EventTranslation>> {
+        Ok(SqlEventTranslation::belonging_to(sql_event)
+            .load::<SqlEventTranslation>(&*self.0)?
+            .into_iter()
+            .map(|sql_translation| sql_translation.into())
+            .collect())
+    }
+
+    fn insert_translations(
+        &self,
+        event_id: &db::SqlId<Event>,
+        translations: HashMap<Locale, EventTranslation>,
+    ) -> QueryResult<()> {
+        use db::schema::event_translations::dsl::event_translations;
+
+        let sql_translations: Vec<SqlEventTranslation> = translations
+            .into_iter()
+            .map(|(locale, translation)| (locale, translation, event_id.clone()).into())
+            .collect();
+        diesel::insert_into(event_translations)
+            .values(&sql_translations)
+            .execute(&*self.0)?;
+
+        Ok(())
+    }
+
+    /// Converts the event, adding its tags.
+    fn event_from_sql(&self, sql_event: SqlEvent) -> QueryResult<(Id<Event>, Event)> {
+        use db::schema::tags::dsl::name;
+
+        let tags: Vec<String> = SqlTag::belonging_to(&sql_event)
+            .order(name)
+            .load::<SqlTag>(&*self.0)?
+            .into_iter()
+            .map(|sql_tag| sql_tag.name)
+            .collect();
+        let (id, event) = sql_event.into();
+
+        Ok((
+            id,
+            Event {
+                tags: Some(tags),
+                ..event
+            },
+        ))
+    }
+
+    fn insert_tags(&self, event_id: &db::SqlId<Event>, new_tags: Vec<String>) -> QueryResult<()> {
+        use db::schema::tags::dsl::tags;
+
+        let sql_tags: Vec<SqlTag> = new_tags
+            .into_iter()
+            .map(|name| SqlTag {
+                event_id: event_id.clone(),
+                name,
+            })
+            .collect();
+        diesel::insert_into(tags)
+            .values(&sql_tags)
+            .execute(&*self.0)?;
+
+        Ok(())
+    }
+
+    /// All tags of published events, to offer filtering by them.
+    pub fn all_tags(&self) -> Vec<String> {
+        use db::schema::tags::dsl::{event_id, name, tags};
+
+        tags.filter(event_id.eq_any(visible_event_ids(&OccurrenceFilter::default())))
+            .select(name)
+            .distinct()
+            .order(name)
+            .load::<String>(&*self.0)
+            .expect("Loading from database failed.")
+    }
+
+    /// The events published after `since`, the newest first. Drafts count from when they are
+    /// published, not from when they were created.
+    pub fn events_published_since(&self, since: NaiveDateTime) -> Vec<(Id<Event>, Event)> {
+        use db::schema::events::dsl::{deleted_at, events, published_at};
+
+        events
+            .filter(deleted_at.is_null())
+            .filter(published_at.gt(since))
+            .filter(published_at.le(db::now()))
+            .order(published_at.desc())
+            .load::<SqlEvent>(&*self.0)
+            .expect("Loading from database failed.")
+            .into_iter()
+            .map(|sql_event| {
+                self.event_from_sql(sql_event)
+                    .expect("Loading from database failed.")
+            })
+            .filter(|(_, event)| event.is_published())
+            .collect()
+    }
+
+    /// Whether the public website shows the event now.
+    pub fn event_is_published(&self, event_id: Id<Event>) -> QueryResult<bool> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::SqlId;
+
+        let sql_event = events
+            .find(SqlId::from(event_id))
+            .filter(deleted_at.is_null())
+            .first::<SqlEvent>(&*self.0)?;
+        let (_, event) = sql_event.into();
+
+        Ok(event.is_published())
+    }
+
+    /// The link secret that shows the event before it is published.
+    pub fn preview_token(&self, event_id: Id<Event>) -> QueryResult<String> {
+        use db::schema::events::dsl::{deleted_at, events, preview_token};
+        use db::SqlId;
+
+        events
+            .find(SqlId::from(event_id))
+            .filter(deleted_at.is_null())
+            .select(preview_token)
+            .first(&*self.0)
+    }
+
+    /// The translations of all events into `locale`, to show them in that language.
+    pub fn event_translations(&self, locale: Locale) -> HashMap<Id<Event>, EventTranslation> {
+        use db::schema::event_translations::dsl::{event_translations, language};
+
+        event_translations
+            .filter(language.eq(locale.code()))
+            .load::<SqlEventTranslation>(&*self.0)
+            .expect("Loading from database failed.")
+            .into_iter()
+            .map(|sql_translation| {
+                let event_id = sql_translation.event_id.clone().into();
+                let (_, translation) = sql_translation.into();
+
+                (event_id, translation)
+            })
+            .collect()
+    }
+}
+
+/// Recurrence rules are expanded at most this many days into the future, even if a later `before`
+/// is requested.
+const RECURRENCE_HORIZON_DAYS: i64 = 365;
+
+impl Store {
+    /// The previous versions of the event, the newest first.
+    pub fn event_history(&self, event_id: Id<Event>) -> QueryResult<Vec<(Id<Revision>, Revision)>> {
+        self.history(db::EVENT_REVISION, &event_id)
+    }
+
+    /// The previous versions of the location, the newest first.
+    pub fn location_history(
+        &self,
+        location_id: Id<Location>,
+    ) -> QueryResult<Vec<(Id<Revision>, Revision)>> {
+        self.history(db::LOCATION_REVISION, &location_id)
+    }
+
+    /// Changes the event back to the revision, including its occurrences, recurrence rules and
+    /// translations. A trashed event is taken out of the trash and a purged one is stored again
+    /// under its previous id, both with the trashed locations where they take place. Otherwise,
+    /// the current version is kept as another revision, so restoring can be undone as well.
+    pub fn restore_event(
+        &self,
+        event_id: Id<Event>,
+        revision_id: Id<Revision>,
+    ) -> QueryResult<EventWithOccurrences> {
+        use db::schema::events::dsl::{created_at, deleted_at, events, published_at};
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let revision = self.revision(db::EVENT_REVISION, &event_id, revision_id)?;
+            let audit = snapshot_audit(&revision.snapshot["event"]);
+            let snapshot: EventWithOccurrences = serde_json::from_value(revision.snapshot)
+                .expect("Invalid revision snapshot in database.");
+
+            let raw_id: SqlId<Event> = event_id.clone().into();
+            let stored = diesel::update(events.find(&raw_id))
+                .set(deleted_at.eq(None::<NaiveDateTime>))
+                .execute(&*self.0)?;
+            if stored > 0 {
+                self.replace_event_with_occurrences(
+                    event_id.clone(),
+                    snapshot,
+                    &OccurrenceFilter::default(),
+                    true,
+                )?;
+            } else {
+                self.insert_event_with_occurrences(event_id.clone(), snapshot)?;
+                if let Some(audit) = audit {
+                    diesel::update(events.find(&raw_id))
+                        .set(created_at.eq(audit.created_at))
+                        .execute(&*self.0)?;
+                    if audit.published_at.is_some() {
+                        diesel::update(events.find(&raw_id))
+                            .set(published_at.eq(audit.published_at))
+                            .execute(&*self.0)?;
+                    }
+                }
+            }
+            self.restore_locations_of_event(&raw_id)?;
+
+            self.read_event_with_occurrences(event_id, &OccurrenceFilter::default())
+        })
+    }
+
+    /// Changes the location back to the revision. A trashed location is taken out of the trash
+    /// and a purged one is stored again under its previous id, but the occurrences deleted with
+    /// it have to be restored with their events.
+    pub fn restore_location(
+        &self,
+        location_id: Id<Location>,
+        revision_id: Id<Revision>,
+    ) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let revision = self.revision(db::LOCATION_REVISION, &location_id, revision_id)?;
+            let audit = snapshot_audit(&revision.snapshot);
+            let snapshot: Location = serde_json::from_value(revision.snapshot)
+                .expect("Invalid revision snapshot in database.");
+
+            let raw_id: SqlId<Location> = location_id.clone().into();
+            let stored = diesel::update(schema.find(&raw_id))
+                .set(deleted_at.eq(None::<NaiveDateTime>))
+                .execute(&*self.0)?;
+            if stored > 0 {
+                self.update(location_id.clone(), snapshot)?;
+            } else {
+                let sql_location: SqlLocation = snapshot.into();
+                let sql_location = SqlLocation {
+                    id: location_id.clone().into(),
+                    created_at: audit.map_or(sql_location.created_at, |audit| audit.created_at),
+                    updated_by: self.editor(),
+                    ..sql_location
+                };
+                diesel::insert_into(schema)
+                    .values(&sql_location)
+                    .execute(&*self.0)?;
+            }
+
+            self.read(location_id)
+        })
+    }
+
+    /// Keeps the event as it is now, with all its occurrences, recurrence rules and translations.
+    /// Only the stored occurrences are kept, as the rules generate the others again.
+    fn record_event_revision(
+        &self,
+        event_id: &Id<Event>,
+        action: RevisionAction,
+    ) -> QueryResult<()> {
+        use db::schema::events::dsl::{deleted_at, events};
+
+        let sql_event = events
+            .find(db::SqlId::from(event_id.clone()))
+            .filter(deleted_at.is_null())
+            .first::<SqlEvent>(&*self.0)?;
+        let snapshot = self.stored_event_with_occurrences(&sql_event)?;
+
+        self.record_revision(db::EVENT_REVISION, event_id, action, &snapshot)
+    }
+
+    fn record_revision<Item, Snapshot: Serialize>(
+        &self,
+        item_kind: &str,
+        item_id: &Id<Item>,
+        action: RevisionAction,
+        snapshot: &Snapshot,
+    ) -> QueryResult<()> {
+        use db::schema::revisions::dsl::revisions;
+
+        let sql_revision = SqlRevision {
+            id: Uuid::new_v4().into(),
+            item_kind: item_kind.to_string(),
+            item_id: item_id.id.as_bytes().to_vec(),
+            action: action.code().to_string(),
+            snapshot: serde_json::to_string(snapshot).unwrap(),
+            created_at: db::now(),
+            created_by: self.editor(),
+        };
+        diesel::insert_into(revisions)
+            .values(&sql_revision)
+            .execute(&*self.0)?;
+
+        Ok(())
+    }
+
+    fn history<Item>(
+        &self,
+        item_kind: &str,
+        item_id: &Id<Item>,
+    ) -> QueryResult<Vec<(Id<Revision>, Revision)>> {
+        use db::schema::revisions::dsl;
+
+        Ok(dsl::revisions
+            .filter(dsl::item_kind.eq(item_kind))
+            .filter(dsl::item_id.eq(item_id.id.as_bytes().to_vec()))
+            .order(dsl::created_at.desc())
+            .load::<SqlRevision>(&*self.0)?
+            .into_iter()
+            .map(|sql_revision| sql_revision.into())
+            .collect())
+    }
+
+    /// Looks up one of the item's revisions. Revisions of other items are not found.
+    fn revision<Item>(
+        &self,
+        item_kind: &str,
+        item_id: &Id<Item>,
+        revision_id: Id<Revision>,
+    ) -> QueryResult<Revision> {
+        use db::schema::revisions::dsl;
+        use db::SqlId;
+
+        let (_, revision) = dsl::revisions
+            .find(SqlId::from(revision_id))
+            .filter(dsl::item_kind.eq(item_kind))
+            .filter(dsl::item_id.eq(item_id.id.as_bytes().to_vec()))
+            .first::<SqlRevision>(&*self.0)?
+            .into();
+
+        Ok(revision)
+    }
+}
+
+/// How long deleted items stay in the trash, unless `trash_days` is configured.
+const DEFAULT_TRASH_DAYS: i64 = 30;
+
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+impl Store {
+    /// Regularly purges the items that have been in the trash for longer than `trash_days` from
+    /// the config, starting when Rocket launches. Each purge takes a connection from the pool and
+    /// returns it afterwards.
+    pub fn trash_fairing() -> AdHoc {
+        AdHoc::on_launch("Trash Purge", |rocket| {
+            let days = rocket
+                .config()
+                .get_int("trash_days")
+                .unwrap_or(DEFAULT_TRASH_DAYS);
+            let pool = db::Pool::of(rocket).expect("The database fairing is not attached.");
+
+            thread::spawn(move || loop {
+                match pool.get() {
+                    Some(conn) => {
+                        let deleted_before = db::now() - chrono::Duration::days(days);
+                        if let Err(err) = Store::new(conn).purge_trash(deleted_before) {
+                            eprintln!("Purging the trash failed: {}", err);
+                        }
+                    }
+                    None => eprintln!("Purging the trash failed: No database connection."),
+                }
+
+                thread::sleep(TRASH_PURGE_INTERVAL);
+            });
+        })
+    }
+
+    /// All trashed events and locations. The events only list their stored occurrences.
+    pub fn trash(&self) -> QueryResult<Trash> {
+        use db::schema::events::dsl::{deleted_at as event_deleted_at, events};
+        use db::schema::locations::dsl::{deleted_at as location_deleted_at, locations};
+
+        let trashed_events = events
+            .filter(event_deleted_at.is_not_null())
+            .load::<SqlEvent>(&*self.0)?
+            .into_iter()
+            .map(|sql_event| {
+                let deleted_at = sql_event
+                    .deleted_at
+                    .expect("Trashed events have a deletion time.");
+                let item = self.stored_event_with_occurrences(&sql_event)?;
+
+                Ok((sql_event.id.into(), Trashed { deleted_at, item }))
+            })
+            .collect::<QueryResult<_>>()?;
+        let trashed_locations = locations
+            .filter(location_deleted_at.is_not_null())
+            .load::<SqlLocation>(&*self.0)?
+            .into_iter()
+            .map(|sql_location| {
+                let deleted_at = sql_location
+                    .deleted_at
+                    .expect("Trashed locations have a deletion time.");
+                let (id, item) = sql_location.into();
+
+                (id, Trashed { deleted_at, item })
+            })
+            .collect();
+
+        Ok(Trash {
+            events: trashed_events,
+            locations: trashed_locations,
+        })
+    }
+
+    /// Takes the event out of the trash, together with the trashed locations where it takes place.
+    pub fn restore_trashed_event(&self, event_id: Id<Event>) -> QueryResult<EventWithOccurrences> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let raw_id: SqlId<Event> = event_id.clone().into();
+            let restored = diesel::update(events.find(&raw_id).filter(deleted_at.is_not_null()))
+                .set(deleted_at.eq(None::<NaiveDateTime>))
+                .execute(&*self.0)?;
+            if restored == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            self.restore_locations_of_event(&raw_id)?;
+
+            self.read_event_with_occurrences(event_id, &OccurrenceFilter::default())
+        })
+    }
+
+    pub fn restore_trashed_location(&self, location_id: Id<Location>) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
+        use db::SqlId;
+
+        let raw_id: SqlId<Location> = location_id.clone().into();
+        let restored = diesel::update(schema.find(&raw_id).filter(deleted_at.is_not_null()))
+            .set(deleted_at.eq(None::<NaiveDateTime>))
+            .execute(&*self.0)?;
+        if restored == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+
+        self.read(location_id)
+    }
+
+    /// Deletes a trashed event for good, together with its occurrences, recurrence rules and
+    /// translations. It can still be restored from its history.
+    pub fn purge_event(&self, event_id: Id<Event>) -> QueryResult<EventWithOccurrences> {
+        use db::schema::events::dsl::{deleted_at, events};
+        use db::SqlId;
+
+        self.0.transaction(|| {
+            let sql_event = events
+                .find(SqlId::from(event_id))
+                .filter(deleted_at.is_not_null())
+                .first::<SqlEvent>(&*self.0)?;
+            let previous = self.stored_event_with_occurrences(&sql_event)?;
+
+            diesel::delete(SqlOccurrence::belonging_to(&sql_event)).execute(&*self.0)?;
+            diesel::delete(SqlRecurrence::belonging_to(&sql_event)).execute(&*self.0)?;
+            diesel::delete(SqlEventTranslation::belonging_to(&sql_event)).execute(&*self.0)?;
+            diesel::delete(SqlTag::belonging_to(&sql_event)).execute(&*self.0)?;
+
+            diesel::delete(&sql_event).execute(&*self.0)?;
+
+            Ok(previous)
+        })
+    }
+
+    /// Deletes a trashed location for good. This fails while trashed events still take place
+    /// there. It can still be restored from its history.
+    pub fn purge_location(&self, location_id: Id<Location>) -> QueryResult<Location> {
+        use db::schema::locations::dsl::deleted_at;
+        use db::SqlId;
+
+        let raw_id: SqlId<Location> = location_id.into();
+        let (_, previous): (Id<Location>, Location) = schema
+            .find(&raw_id)
+            .filter(deleted_at.is_not_null())
+            .first::<SqlLocation>(&*self.0)?
+            .into();
+
+        diesel::delete(schema.find(&raw_id)).execute(&*self.0)?;
+
+        Ok(previous)
+    }
+
+    /// Purges everything moved to the trash before `deleted_before`. Locations where trashed
+    /// events still take place are kept until those are purged, too.
+    pub fn purge_trash(&self, deleted_before: NaiveDateTime) -> QueryResult<()> {
+        use db::schema::events::dsl::{deleted_at as event_deleted_at, events, id as event_id};
+        use db::schema::locations::dsl::{deleted_at, id};
+        use db::schema::occurrences::dsl as occurrences;
+        use db::schema::recurrences::dsl as recurrences;
+
+        self.0.transaction(|| {
+            let expired_events = events
+                .filter(event_deleted_at.lt(deleted_before))
+                .select(event_id)
+                .load::<db::SqlId<Event>>(&*self.0)?;
+            for expired in expired_events {
+                self.purge_event(expired.into())?;
+            }
+
+            let occurring = occurrences::occurrences.select(occurrences::location_id);
+            let recurring = recurrences::recurrences.select(recurrences::location_id);
+            diesel::delete(
+                schema
+                    .filter(deleted_at.lt(deleted_before))
+                    .filter(id.ne_all(occurring))
+                    .filter(id.ne_all(recurring)),
+            )
+            .execute(&*self.0)?;
+
+            Ok(())
+        })
+    }
+
+    fn restore_locations_of_event(&self, event_id: &db::SqlId<Event>) -> QueryResult<()> {
+        use db::schema::locations::dsl::{deleted_at, id};
+        use db::schema::occurrences::dsl as occurrences;
+        use db::schema::recurrences::dsl as recurrences;
+
+        let occurring = occurrences::occurrences
+            .filter(occurrences::event_id.eq(event_id))
+            .select(occurrences::location_id);
+        let recurring = recurrences::recurrences
+            .filter(recurrences::event_id.eq(event_id))
+            .select(recurrences::location_id);
+        diesel::update(
+            schema
+                .filter(deleted_at.is_not_null())
+                .filter(id.eq_any(occurring).or(id.eq_any(recurring))),
+        )
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .execute(&*self.0)?;
+
+        Ok(())
+    }
+}
+
+/// The audit of an item in a revision's snapshot. Items received from clients never have one,
+/// so it is not deserialized with the item.
+fn snapshot_audit(item: &serde_json::Value) -> Option<Audit> {
+    serde_json::from_value(item.get("audit")?.clone()).ok()
+}
+
+fn expand_recurrence(
+    id: &Id<Recurrence>,
+    recurrence: &Recurrence,
+    filter: &OccurrenceFilter,
+) -> Vec<(Id<Occurrence>, OccurrenceWithLocation)> {
+    let first = &recurrence.first;
+    let from = filter
+        .after
+        .map_or(first.occurrence.start.date(), |after| after.date());
+    // Rules without an end would otherwise be expanded up to the `before` of the request.
+    let horizon =
+        chrono::Local::today().naive_local() + chrono::Duration::days(RECURRENCE_HORIZON_DAYS);
+    let to = filter
+        .before
+        .map_or(horizon, |before| before.date().min(horizon));
+
+    recurrence
+        .dates(from, to)
+        .into_iter()
+        .map(|date| {
+            let occurrence_id = generated_id(id, date);
+            let occurrence = OccurrenceWithLocation {
+                id: Some(occurrence_id.clone()),
+                occurrence: Occurrence {
+                    start: NaiveDateTime::new(date, first.occurrence.start.time()),
+                    duration: first.occurrence.duration,
+                },
+                location_id: first.location_id.clone(),
+                status: Some(OccurrenceStatus::default()),
+                overrides: Some(OccurrenceOverrides::default()),
+                audit: None,
+            };
+
+            (occurrence_id, occurrence)
+        })
+        .filter(|(_, occurrence)| filter.matches(&occurrence.occurrence.start))
+        .collect()
+}
+
+/// Deriving the id from the rule and the date keeps it stable across requests.
+fn generated_id(recurrence_id: &Id<Recurrence>, date: NaiveDate) -> Id<Occurrence> {
+    Uuid::new_v5(&recurrence_id.id, date.to_string().as_bytes()).into()
+}
+
+fn is_generated(occurrence: &OccurrenceWithLocation, recurrences: &[Recurrence]) -> bool {
+    recurrences
+        .iter()
+        .any(|recurrence| recurrence.generates(occurrence))
+}
+
+impl Store {
+    pub fn has_users(&self) -> QueryResult<bool> {
+        use db::schema::users::dsl::users;
+
+        let count: i64 = users.count().get_result(&*self.0)?;
+        Ok(count > 0)
+    }
+
+    pub fn create_user(&self, user: User, password: &str) -> QueryResult<Id<User>> {
+        use db::schema::users::dsl::users;
+
+        let sql_user = SqlUser {
+            password_hash: hash_password(password),
+            ..user.into()
+        };
+        diesel::insert_into(users)
+            .values(&sql_user)
+            .execute(&*self.0)?;
+
+        Ok(sql_user.id.into())
+    }
+
+    /// Returns the user's id if `password` matches the one stored for the user called `user_name`.
+    pub fn authenticate(&self, user_name: &str, password: &str) -> QueryResult<Option<Id<User>>> {
+        use db::schema::users::dsl::{name, users};
+
+        let maybe_user = users
+            .filter(name.eq(user_name))
+            .first::<SqlUser>(&*self.0)
+            .optional()?;
+
+        Ok(maybe_user
+            .filter(|sql_user| bcrypt::verify(password, &sql_user.password_hash).unwrap_or(false))
+            .map(|sql_user| sql_user.id.into()))
+    }
+
+    /// Whether the location exists and is not in the trash.
+    pub fn location_exists(&self, id: Id<Location>) -> QueryResult<bool> {
+        use db::schema::locations::dsl::{deleted_at, locations};
+        use db::SqlId;
+
+        diesel::select(diesel::dsl::exists(
+            locations.find(SqlId::from(id)).filter(deleted_at.is_null()),
+        ))
+        .get_result(&*self.0)
+    }
+
+    pub fn user_exists(&self, id: Id<User>) -> QueryResult<bool> {
+        use db::schema::users::dsl::users;
+        use db::SqlId;
+
+        diesel::select(diesel::dsl::exists(users.find(SqlId::from(id)))).get_result(&*self.0)
+    }
+
+    /// Creates a token that allows the user to set a new password.
+    pub fn create_invitation(&self, user_id: Id<User>) -> QueryResult<Invitation> {
+        use db::schema::invitations::dsl::invitations;
+        use db::schema::users::dsl::users;
+        use db::SqlId;
+
+        let sql_user = users
+            .find(SqlId::from(user_id))
+            .first::<SqlUser>(&*self.0)?;
+
+        let sql_invitation = SqlInvitation {
+            token: thread_rng().sample_iter(&Alphanumeric).take(32).collect(),
+            user_id: sql_user.id,
+            expires_at: chrono::Local::now().naive_local()
+                + chrono::Duration::days(INVITATION_VALIDITY_DAYS),
+        };
+        diesel::insert_into(invitations)
+            .values(&sql_invitation)
+            .execute(&*self.0)?;
+
+        Ok(Invitation {
+            token: sql_invitation.token,
+            expires_at: sql_invitation.expires_at,
+        })
+    }
+
+    /// Sets the password of the invited user. An invitation can only be accepted once.
+    pub fn accept_invitation(&self, acceptance: &Acceptance) -> QueryResult<Id<User>> {
+        self.0.transaction(|| {
+            use db::schema::invitations::dsl::{expires_at, invitations};
+            use db::schema::users::dsl::{password_hash, users};
+
+            let sql_invitation = invitations
+                .find(&acceptance.token)
+                .filter(expires_at.gt(chrono::Local::now().naive_local()))
+                .first::<SqlInvitation>(&*self.0)?;
+
+            diesel::update(users.find(&sql_invitation.user_id))
+                .set(password_hash.eq(hash_password(&acceptance.password)))
+                .execute(&*self.0)?;
+            diesel::delete(&sql_invitation).execute(&*self.0)?;
+
+            Ok(sql_invitation.user_id.into())
+        })
+    }
+}
+
+const INVITATION_VALIDITY_DAYS: i64 = 7;
+
+fn hash_password(password: &str) -> String {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).expect("Hashing the password failed.")
+}
+
+/// Groups the entries by the date they start on, each day ordered by start.
+fn group_by_date(
+    entries: impl Iterator<Item = OccurrenceWithEvent>,
+) -> BTreeMap<NaiveDate, Vec<OccurrenceWithEvent>> {
+    let mut occurrences_by_date: BTreeMap<NaiveDate, Vec<OccurrenceWithEvent>> = BTreeMap::new();
+    for entry in entries {
+        occurrences_by_date
+            .entry(entry.occurrence.occurrence.start.date())
+            .or_default()
+            .push(entry);
+    }
+
+    for entries in occurrences_by_date.values_mut() {
+        entries.sort_by_key(|entry| entry.occurrence.occurrence.start);
+    }
+
+    occurrences_by_date
+}
+
+/// The ids of the events whose occurrences `filter` lets through: never trashed ones, and only
+/// published ones unless the filter includes unpublished events.
+fn visible_event_ids(
+    filter: &OccurrenceFilter,
+) -> db::schema::events::BoxedQuery<'static, diesel::sqlite::Sqlite, diesel::sql_types::Binary> {
+    use db::schema::events::dsl::{deleted_at, events, id, publication, publish_at};
+
+    let live = events.select(id).filter(deleted_at.is_null()).into_boxed();
+    if filter.unpublished {
+        return live;
+    }
+
+    let now = db::now();
+    live.filter(
+        publication.eq(Publication::Published.code()).or(publication
+            .eq(Publication::Scheduled { publish_at: now }.code())
+            .and(publish_at.le(now))),
+    )
+}
 
 fn apply_occurrence_filter(
     filter: &OccurrenceFilter,
@@ -491,6 +2342,11 @@ impl<'a, 'r> FromRequest<'a, 'r> for Store {
     type Error = <db::Connection as FromRequest<'a, 'r>>::Error;
 
     fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
-        db::Connection::from_request(request).map(Store)
+        db::Connection::from_request(request).map(|conn| {
+            let mut store = Store::new(conn);
+            store.1 = crate::auth::session_user(request);
+
+            store
+        })
     }
 }
@@ -1,15 +1,230 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use super::Id;
+use super::{Id, OccurrenceFilter};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)] // Hash, PartialEq, and Eq required, because Derive does not understand bounds on `Id`'s PhantomData. See https://github.com/rust-lang/rust/issues/26925
 pub struct Event {
     pub title: String,
     pub teaser: String,
     pub description: String,
+    /// `null` removes the price. Omitting it on update keeps the existing price.
+    #[serde(
+        default,
+        deserialize_with = "explicit_null",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub price: Option<Option<Price>>,
+    /// `null` removes the category. Omitting it on update keeps the existing category.
+    #[serde(
+        default,
+        deserialize_with = "explicit_null",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub category: Option<Option<Category>>,
+    /// Free tags like `beginner-friendly`. Omitting them on update keeps the existing tags.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// New events without it are published right away. Omitting it on update keeps the existing
+    /// publication.
+    #[serde(default)]
+    pub publication: Option<Publication>,
+    /// Set by the store and ignored when received.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
+}
+
+impl Event {
+    pub fn current_price(&self) -> Option<&Price> {
+        self.price.as_ref().and_then(Option::as_ref)
+    }
+
+    pub fn current_category(&self) -> Option<Category> {
+        self.category.flatten()
+    }
+
+    /// Whether the public website and feeds show the event now.
+    pub fn is_published(&self) -> bool {
+        self.publication
+            .unwrap_or_default()
+            .is_public(chrono::Local::now().naive_local())
+    }
+
+    /// The event in another language. Texts that are not translated are kept.
+    pub fn localized(&self, translation: Option<&EventTranslation>) -> Event {
+        let mut event = self.clone();
+        if let Some(translation) = translation {
+            let translated = |text: &String, fallback: &mut String| {
+                if !text.trim().is_empty() {
+                    *fallback = text.clone();
+                }
+            };
+            translated(&translation.title, &mut event.title);
+            translated(&translation.teaser, &mut event.teaser);
+            translated(&translation.description, &mut event.description);
+        }
+
+        event
+    }
+}
+
+/// Tells an explicit `null`, which is `Some(None)`, apart from an omitted field, which is `None`.
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Whether the public sees an event. Drafts can be shared with a preview link instead.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Publication {
+    Draft,
+    /// Published automatically at that local time.
+    Scheduled {
+        publish_at: NaiveDateTime,
+    },
+    #[default]
+    Published,
+}
+
+impl Publication {
+    pub fn code(self) -> &'static str {
+        match self {
+            Publication::Draft => "draft",
+            Publication::Scheduled { .. } => "scheduled",
+            Publication::Published => "published",
+        }
+    }
+
+    /// `None` for unknown codes and for scheduled publications without a time.
+    pub fn from_code(code: &str, publish_at: Option<NaiveDateTime>) -> Option<Publication> {
+        match code {
+            "draft" => Some(Publication::Draft),
+            "scheduled" => publish_at.map(|publish_at| Publication::Scheduled { publish_at }),
+            "published" => Some(Publication::Published),
+            _ => None,
+        }
+    }
+
+    pub fn publish_at(self) -> Option<NaiveDateTime> {
+        match self {
+            Publication::Scheduled { publish_at } => Some(publish_at),
+            _ => None,
+        }
+    }
+
+    pub fn is_public(self, now: NaiveDateTime) -> bool {
+        match self {
+            Publication::Draft => false,
+            Publication::Scheduled { publish_at } => publish_at <= now,
+            Publication::Published => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromFormValue, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Social,
+    Class,
+    Workshop,
+    Festival,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Social,
+        Category::Class,
+        Category::Workshop,
+        Category::Festival,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Category::Social => "social",
+            Category::Class => "class",
+            Category::Workshop => "workshop",
+            Category::Festival => "festival",
+        }
+    }
+}
+
+/// What attending an event costs, e. g. "Free", "Donation" or "20 € – 25 €".
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Price {
+    #[serde(flatten)]
+    pub kind: PriceKind,
+    /// Further details, e. g. "Reduced for students".
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Amounts are given in the smallest unit of the currency, e. g. cents.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriceKind {
+    Free,
+    Donation,
+    /// No entry fee, but guests are expected to buy a drink.
+    Consumption,
+    Fixed {
+        amount: u32,
+        currency: Currency,
+    },
+    Range {
+        min: u32,
+        max: u32,
+        currency: Currency,
+    },
+}
+
+/// An ISO 4217 currency code, e. g. `EUR`.
+pub type Currency = String;
+
+/// The languages of the website. Events are written in the default language and may be
+/// translated into the others.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    De,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::De, Locale::En];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::De => "de",
+            Locale::En => "en",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Locale> {
+        Locale::ALL
+            .iter()
+            .cloned()
+            .find(|locale| locale.code().eq_ignore_ascii_case(code))
+    }
+}
+
+/// An event's texts in a language other than the default one. Empty texts fall back to the
+/// default language.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct EventTranslation {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub teaser: String,
+    #[serde(default)]
+    pub description: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)] // Hash, PartialEq, and Eq required, because Derive does not understand bounds on `Id`'s PhantomData. See https://github.com/rust-lang/rust/issues/26925
@@ -22,10 +237,9 @@ type Duration = u32;
 
 impl Occurrence {
     pub fn end(&self) -> NaiveDateTime {
-        use std::convert::TryInto;
         use std::ops::Add;
         self.start
-            .add(chrono::Duration::minutes(self.duration.try_into().unwrap()))
+            .add(chrono::Duration::minutes(i64::from(self.duration)))
     }
 }
 
@@ -33,6 +247,42 @@ impl Occurrence {
 pub struct Location {
     pub name: String,
     pub address: String,
+    /// Set by the store and ignored when received.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)] // Hash, PartialEq, and Eq required, because Derive does not understand bounds on `Id`'s PhantomData. See https://github.com/rust-lang/rust/issues/26925
+pub struct User {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Invitation {
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// The password an invited user chooses for themselves.
+#[derive(Deserialize, FromForm, Debug, Clone)]
+pub struct Acceptance {
+    pub token: String,
+    pub password: String,
+}
+
+/// A static content page, e. g. "Get started". Only published pages are shown on the website.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Page {
+    /// The page is shown at `/<slug>`.
+    pub slug: String,
+    pub title: String,
+    /// Markdown
+    pub body: String,
+    #[serde(default)]
+    pub published: bool,
+    /// Set by the store and ignored when received.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -41,11 +291,109 @@ pub struct LocationWithOccurrences {
     pub occurrences: HashMap<Id<Occurrence>, Occurrence>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)] // Hash, PartialEq, and Eq required, because Derive does not understand bounds on `Id`'s PhantomData. See https://github.com/rust-lang/rust/issues/26925
 pub struct OccurrenceWithLocation {
+    /// Stays the same when the occurrence is changed. New occurrences do not have one yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id<Occurrence>>,
     #[serde(flatten)]
     pub occurrence: Occurrence,
     pub location_id: Id<Location>,
+    /// Omitting it on update keeps the existing status. New occurrences are scheduled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<OccurrenceStatus>,
+    /// Omitting them on update keeps the existing overrides.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<OccurrenceOverrides>,
+    /// Set by the store and ignored when received. Generated occurrences do not have one.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
+}
+
+/// Details that only apply to a single occurrence, e. g. a live band. Empty fields keep the
+/// event's texts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct OccurrenceOverrides {
+    /// Appended to the event's title, e. g. "mit Live-Band".
+    #[serde(default)]
+    pub title_suffix: String,
+    /// Replaces the event's teaser and is written in Markdown as well.
+    #[serde(default)]
+    pub teaser: String,
+    /// Shown in addition to the event's texts, e. g. "Im kleinen Saal".
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Whether an occurrence takes place as planned. Cancelled occurrences stay listed, so that
+/// people who planned to come notice the change.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct OccurrenceStatus {
+    pub state: OccurrenceState,
+    /// E. g. the reason for cancelling or where the occurrence moved to.
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OccurrenceState {
+    #[default]
+    Scheduled,
+    Cancelled,
+    Moved,
+    Tentative,
+}
+
+impl OccurrenceState {
+    pub fn code(self) -> &'static str {
+        match self {
+            OccurrenceState::Scheduled => "scheduled",
+            OccurrenceState::Cancelled => "cancelled",
+            OccurrenceState::Moved => "moved",
+            OccurrenceState::Tentative => "tentative",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<OccurrenceState> {
+        use OccurrenceState::*;
+
+        [Scheduled, Cancelled, Moved, Tentative]
+            .iter()
+            .cloned()
+            .find(|state| state.code() == code)
+    }
+}
+
+impl OccurrenceWithLocation {
+    /// The status, where occurrences without one are scheduled.
+    pub fn current_status(&self) -> OccurrenceStatus {
+        self.status.clone().unwrap_or_default()
+    }
+
+    pub fn current_overrides(&self) -> OccurrenceOverrides {
+        self.overrides.clone().unwrap_or_default()
+    }
+
+    /// The event as it takes place at this occurrence.
+    pub fn apply_overrides(&self, event: &Event) -> Event {
+        let overrides = self.current_overrides();
+        let mut event = event.clone();
+        if !overrides.title_suffix.trim().is_empty() {
+            event.title = format!("{} – {}", event.title, overrides.title_suffix.trim());
+        }
+        if !overrides.teaser.trim().is_empty() {
+            event.teaser = overrides.teaser;
+        }
+
+        event
+    }
+
+    /// Whether both take place at the same time and location, regardless of their ids, status
+    /// and overrides.
+    pub fn same_as(&self, other: &OccurrenceWithLocation) -> bool {
+        self.occurrence == other.occurrence && self.location_id == other.location_id
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -54,14 +402,502 @@ pub struct Overview {
     pub events: HashMap<Id<Event>, EventWithOccurrences>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct EventWithOccurrences {
     pub event: Event,
     pub occurrences: Vec<OccurrenceWithLocation>,
+    /// Rules generating further occurrences. Omitting them on update keeps the existing rules.
+    #[serde(default)]
+    pub recurrences: Option<Vec<Recurrence>>,
+    /// Omitting them on update keeps the existing translations.
+    #[serde(default)]
+    pub translations: Option<HashMap<Locale, EventTranslation>>,
+}
+
+impl EventWithOccurrences {
+    /// The first of the listed occurrences that is still upcoming.
+    pub fn next_occurrence(&self) -> Option<&OccurrenceWithLocation> {
+        let upcoming = OccurrenceFilter::upcoming();
+
+        self.occurrences
+            .iter()
+            .filter(|occurrence| upcoming.matches(&occurrence.occurrence.start))
+            .min_by_key(|occurrence| occurrence.occurrence.start)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)] // Hash, PartialEq, and Eq required, because Derive does not understand bounds on `Id`'s PhantomData. See https://github.com/rust-lang/rust/issues/26925
+pub struct Recurrence {
+    /// Stays the same when the rule is changed, so the occurrences it generates keep their ids.
+    /// New rules do not have one yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id<Recurrence>>,
+    #[serde(flatten)]
+    pub first: FirstOccurrence,
+    pub frequency: Frequency,
+    pub until: Option<NaiveDate>,
+    pub count: Option<u32>,
+    #[serde(default)]
+    pub exceptions: Vec<NaiveDate>,
+}
+
+/// The first occurrence of a recurrence rule. All following ones share its time, duration and
+/// location. The generated occurrences are scheduled and have no overrides, unless they are
+/// changed one by one.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct FirstOccurrence {
+    #[serde(flatten)]
+    pub occurrence: Occurrence,
+    pub location_id: Id<Location>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    /// On the `nth` weekday of the month, where the weekday is the one of the first occurrence.
+    /// Negative values count from the end of the month, e. g. `-1` is the last one.
+    MonthlyNthWeekday {
+        nth: i8,
+    },
+}
+
+impl Recurrence {
+    /// Lists the dates between `from` and `to` (both inclusive) on which the rule generates an occurrence.
+    pub fn dates(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let first = self.first.occurrence.start.date();
+        let last = match self.until {
+            Some(until) if until < to => until,
+            _ => to,
+        };
+
+        let mut dates = Vec::new();
+        // Like in iCalendar, excluded dates still count towards the `count`.
+        for (index, date) in self.frequency.candidates(first, last).enumerate() {
+            if self.count.map_or(false, |count| index as u32 >= count) {
+                break;
+            }
+            if date >= from && !self.exceptions.contains(&date) {
+                dates.push(date);
+            }
+        }
+
+        dates
+    }
+
+    /// Whether both generate the same occurrences, regardless of their ids.
+    pub fn same_as(&self, other: &Recurrence) -> bool {
+        self.first == other.first
+            && self.frequency == other.frequency
+            && self.until == other.until
+            && self.count == other.count
+            && self.exceptions == other.exceptions
+    }
+
+    /// Whether `occurrence` is one of the occurrences generated by this rule.
+    pub fn generates(&self, occurrence: &OccurrenceWithLocation) -> bool {
+        let start = occurrence.occurrence.start;
+
+        occurrence.location_id == self.first.location_id
+            && occurrence.occurrence.duration == self.first.occurrence.duration
+            && start.time() == self.first.occurrence.start.time()
+            && !self.dates(start.date(), start.date()).is_empty()
+    }
+}
+
+impl Frequency {
+    fn candidates(self, first: NaiveDate, last: NaiveDate) -> Box<dyn Iterator<Item = NaiveDate>> {
+        use chrono::Duration;
+
+        // The dates end before the last date chrono can represent.
+        match self {
+            Frequency::Weekly => Box::new(
+                (0..)
+                    .map_while(move |week| first.checked_add_signed(Duration::weeks(week)))
+                    .take_while(move |date| *date <= last),
+            ),
+            Frequency::Biweekly => Box::new(
+                (0..)
+                    .map_while(move |week| first.checked_add_signed(Duration::weeks(2 * week)))
+                    .take_while(move |date| *date <= last),
+            ),
+            Frequency::MonthlyNthWeekday { nth } => Box::new(
+                (0..)
+                    .map_while(move |month| first_of_month(first, month))
+                    .take_while(move |month_start| *month_start <= last)
+                    .filter_map(move |month_start| {
+                        nth_weekday_of_month(month_start, first.weekday(), nth)
+                    })
+                    .filter(move |date| *date >= first && *date <= last),
+            ),
+        }
+    }
+}
+
+/// The first day of the month that lies `offset` months after the month of `date`, if chrono can
+/// represent it.
+fn first_of_month(date: NaiveDate, offset: i32) -> Option<NaiveDate> {
+    let month_index = (date.year() * 12 + date.month0() as i32).checked_add(offset)?;
+    NaiveDate::from_ymd_opt(
+        month_index.div_euclid(12),
+        month_index.rem_euclid(12) as u32 + 1,
+        1,
+    )
+}
+
+fn nth_weekday_of_month(
+    month_start: NaiveDate,
+    weekday: chrono::Weekday,
+    nth: i8,
+) -> Option<NaiveDate> {
+    let days_from_monday = |date: NaiveDate| date.weekday().num_days_from_monday() as i32;
+    let weekday = weekday.num_days_from_monday() as i32;
+
+    match nth.cmp(&0) {
+        Ordering::Greater => {
+            let first_match = (weekday - days_from_monday(month_start) + 7) % 7;
+            let day = 1 + first_match + 7 * (i32::from(nth) - 1);
+            NaiveDate::from_ymd_opt(month_start.year(), month_start.month(), day as u32)
+        }
+        Ordering::Less => {
+            let month_end = first_of_month(month_start, 1)?.pred();
+            let last_match = (days_from_monday(month_end) - weekday + 7) % 7;
+            let day = month_end.day() as i32 - last_match - 7 * (-i32::from(nth) - 1);
+            if day < 1 {
+                None
+            } else {
+                NaiveDate::from_ymd_opt(month_start.year(), month_start.month(), day as u32)
+            }
+        }
+        Ordering::Equal => None,
+    }
+}
+
+/// When an item was stored and when and by whom it was last changed.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Audit {
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// `None` for changes without a logged in user, e. g. by the initial migration.
+    pub updated_by: Option<Id<User>>,
+    /// When an event became or becomes public. Only set for published and scheduled events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<NaiveDateTime>,
+}
+
+/// A previous version of an event or location, kept whenever it is changed or deleted.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Revision {
+    pub action: RevisionAction,
+    /// The item as it was before, e. g. an `EventWithOccurrences` including all occurrences.
+    pub snapshot: serde_json::Value,
+    /// When the item was changed.
+    pub created_at: NaiveDateTime,
+    /// `None` for changes without a logged in user, e. g. by a fairing.
+    pub created_by: Option<Id<User>>,
+}
+
+/// What happened to the item after the revision was taken.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionAction {
+    Update,
+    Delete,
+}
+
+impl RevisionAction {
+    pub fn code(self) -> &'static str {
+        match self {
+            RevisionAction::Update => "update",
+            RevisionAction::Delete => "delete",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<RevisionAction> {
+        use RevisionAction::*;
+
+        [Update, Delete]
+            .iter()
+            .cloned()
+            .find(|action| action.code() == code)
+    }
+}
+
+/// Deleted events and locations, which can be restored until they are purged.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Trash {
+    pub events: HashMap<Id<Event>, Trashed<EventWithOccurrences>>,
+    pub locations: HashMap<Id<Location>, Trashed<Location>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Trashed<Item> {
+    pub deleted_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub item: Item,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct OccurrenceWithEvent {
+    pub id: Id<Occurrence>,
     pub occurrence: OccurrenceWithLocation,
+    pub event_id: Id<Event>,
     pub event: Event,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Weekday;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    fn occurrence(day: NaiveDate) -> OccurrenceWithLocation {
+        OccurrenceWithLocation {
+            id: None,
+            occurrence: Occurrence {
+                start: day.and_hms(20, 0, 0),
+                duration: 120,
+            },
+            location_id: Uuid::nil().into(),
+            status: None,
+            overrides: None,
+            audit: None,
+        }
+    }
+
+    fn recurrence(first: NaiveDate, frequency: Frequency) -> Recurrence {
+        let first = occurrence(first);
+
+        Recurrence {
+            id: None,
+            first: FirstOccurrence {
+                occurrence: first.occurrence,
+                location_id: first.location_id,
+            },
+            frequency,
+            until: None,
+            count: None,
+            exceptions: vec![],
+        }
+    }
+
+    fn social() -> Event {
+        Event {
+            title: "Lindy Hop Social".to_string(),
+            teaser: "Tanzen".to_string(),
+            description: "Social Dance".to_string(),
+            price: None,
+            category: None,
+            tags: None,
+            publication: None,
+            audit: None,
+        }
+    }
+
+    #[test]
+    fn overrides_extend_the_title_and_replace_the_teaser() {
+        let event = social();
+        let occurrence = OccurrenceWithLocation {
+            overrides: Some(OccurrenceOverrides {
+                title_suffix: " mit Live-Band ".to_string(),
+                teaser: "Tanzen zur Musik der Band".to_string(),
+                note: "Im kleinen Saal".to_string(),
+            }),
+            ..occurrence(date(2019, 7, 1))
+        };
+
+        let applied = occurrence.apply_overrides(&event);
+        assert_eq!(applied.title, "Lindy Hop Social – mit Live-Band");
+        assert_eq!(applied.teaser, "Tanzen zur Musik der Band");
+        assert_eq!(applied.description, event.description);
+    }
+
+    #[test]
+    fn empty_overrides_keep_the_event_texts() {
+        let event = social();
+        let occurrence = OccurrenceWithLocation {
+            overrides: Some(OccurrenceOverrides {
+                title_suffix: " ".to_string(),
+                ..OccurrenceOverrides::default()
+            }),
+            ..occurrence(date(2019, 7, 1))
+        };
+
+        let applied = occurrence.apply_overrides(&event);
+        assert_eq!(applied.title, event.title);
+        assert_eq!(applied.teaser, event.teaser);
+    }
+
+    #[test]
+    fn recurrences_only_list_the_fields_of_the_rule() {
+        let weekly = Recurrence {
+            id: Some(Uuid::nil().into()),
+            ..recurrence(date(2019, 7, 1), Frequency::Weekly)
+        };
+
+        let json = serde_json::to_value(&weekly).expect("Serializing failed.");
+        let mut fields: Vec<&String> = json.as_object().map_or(vec![], |map| map.keys().collect());
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec![
+                "count",
+                "duration",
+                "exceptions",
+                "frequency",
+                "id",
+                "location_id",
+                "start",
+                "until"
+            ]
+        );
+        assert_eq!(
+            serde_json::from_value::<Recurrence>(json).expect("Deserializing failed."),
+            weekly
+        );
+    }
+
+    #[test]
+    fn weekly_dates_start_at_the_first_occurrence() {
+        let weekly = recurrence(date(2019, 7, 1), Frequency::Weekly);
+
+        assert_eq!(
+            weekly.dates(date(2019, 6, 1), date(2019, 7, 31)),
+            vec![
+                date(2019, 7, 1),
+                date(2019, 7, 8),
+                date(2019, 7, 15),
+                date(2019, 7, 22),
+                date(2019, 7, 29),
+            ]
+        );
+        assert_eq!(
+            weekly.dates(date(2019, 7, 10), date(2019, 7, 22)),
+            vec![date(2019, 7, 15), date(2019, 7, 22)]
+        );
+    }
+
+    #[test]
+    fn biweekly_dates_skip_every_other_week() {
+        let biweekly = recurrence(date(2019, 7, 1), Frequency::Biweekly);
+
+        assert_eq!(
+            biweekly.dates(date(2019, 7, 1), date(2019, 7, 31)),
+            vec![date(2019, 7, 1), date(2019, 7, 15), date(2019, 7, 29)]
+        );
+    }
+
+    #[test]
+    fn dates_end_at_until() {
+        let weekly = Recurrence {
+            until: Some(date(2019, 7, 15)),
+            ..recurrence(date(2019, 7, 1), Frequency::Weekly)
+        };
+
+        assert_eq!(
+            weekly.dates(date(2019, 7, 1), date(2019, 12, 31)),
+            vec![date(2019, 7, 1), date(2019, 7, 8), date(2019, 7, 15)]
+        );
+    }
+
+    #[test]
+    fn excluded_dates_count_towards_count() {
+        let weekly = Recurrence {
+            count: Some(3),
+            exceptions: vec![date(2019, 7, 8)],
+            ..recurrence(date(2019, 7, 1), Frequency::Weekly)
+        };
+
+        assert_eq!(
+            weekly.dates(date(2019, 7, 1), date(2019, 12, 31)),
+            vec![date(2019, 7, 1), date(2019, 7, 15)]
+        );
+    }
+
+    #[test]
+    fn monthly_dates_skip_months_without_the_nth_weekday() {
+        let fifth_monday = recurrence(date(2019, 7, 29), Frequency::MonthlyNthWeekday { nth: 5 });
+
+        assert_eq!(
+            fifth_monday.dates(date(2019, 7, 1), date(2019, 12, 31)),
+            vec![date(2019, 7, 29), date(2019, 9, 30), date(2019, 12, 30)]
+        );
+    }
+
+    #[test]
+    fn monthly_dates_count_from_the_end_for_negative_nth() {
+        let last_monday = recurrence(date(2019, 7, 29), Frequency::MonthlyNthWeekday { nth: -1 });
+
+        assert_eq!(
+            last_monday.dates(date(2019, 7, 1), date(2019, 9, 30)),
+            vec![date(2019, 7, 29), date(2019, 8, 26), date(2019, 9, 30)]
+        );
+    }
+
+    #[test]
+    fn dates_end_at_the_last_representable_date() {
+        let last = NaiveDate::MAX;
+        let weekly = recurrence(last - chrono::Duration::days(10), Frequency::Weekly);
+        let monthly = recurrence(
+            last - chrono::Duration::days(40),
+            Frequency::MonthlyNthWeekday { nth: -1 },
+        );
+
+        assert_eq!(weekly.dates(date(2019, 1, 1), last).len(), 2);
+        assert!(monthly.dates(date(2019, 1, 1), last).len() <= 2);
+    }
+
+    #[test]
+    fn nth_weekday_counts_from_the_start_of_the_month() {
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 7, 1), Weekday::Mon, 1),
+            Some(date(2019, 7, 1))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 7, 1), Weekday::Mon, 5),
+            Some(date(2019, 7, 29))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 8, 1), Weekday::Mon, 5),
+            None
+        );
+    }
+
+    #[test]
+    fn nth_weekday_counts_from_the_end_of_the_month_for_negative_nth() {
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 2, 1), Weekday::Thu, -1),
+            Some(date(2019, 2, 28))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2020, 2, 1), Weekday::Sat, -1),
+            Some(date(2020, 2, 29))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 12, 1), Weekday::Tue, -1),
+            Some(date(2019, 12, 31))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 7, 1), Weekday::Mon, -5),
+            Some(date(2019, 7, 1))
+        );
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 8, 1), Weekday::Mon, -5),
+            None
+        );
+    }
+
+    #[test]
+    fn nth_weekday_is_none_for_zero() {
+        assert_eq!(
+            nth_weekday_of_month(date(2019, 7, 1), Weekday::Mon, 0),
+            None
+        );
+    }
+}
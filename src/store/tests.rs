@@ -0,0 +1,1075 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate};
+use rocket::config::{Config, Environment, Value};
+use serde_json::json;
+
+use super::*;
+
+/// A store backed by a fresh in-memory database. The pool holds a single connection,
+/// because every connection to `:memory:` opens a separate database.
+fn store() -> Store {
+    let mut database = HashMap::new();
+    database.insert("url", Value::from(":memory:"));
+    database.insert("pool_size", Value::from(1));
+    let mut databases = HashMap::new();
+    databases.insert("sqlite_database", Value::from(database));
+
+    let config = Config::build(Environment::Development)
+        .extra("databases", databases)
+        .finalize()
+        .expect("Invalid test config.");
+    let rocket = rocket::custom(config).attach(Store::fairing());
+
+    Store::get_one(&rocket).expect("Database connection failed.")
+}
+
+/// Everything stored, to compare the database before and after a failed write.
+fn snapshot(store: &Store) -> serde_json::Value {
+    let locations: HashMap<Id<Location>, Location> = store.all();
+
+    json!({
+        "locations": locations,
+        "events": store.all_events_with_occurrences(&OccurrenceFilter::default()),
+    })
+}
+
+fn location(store: &Store) -> Id<Location> {
+    store
+        .create(Location {
+            name: "Chico Mendes".to_string(),
+            address: "Pontstraße 41, 52062 Aachen".to_string(),
+            audit: None,
+        })
+        .expect("Creating the location failed.")
+}
+
+fn occurrence(day: u32, location_id: Id<Location>) -> OccurrenceWithLocation {
+    OccurrenceWithLocation {
+        id: None,
+        occurrence: Occurrence {
+            start: NaiveDate::from_ymd(2019, 7, day).and_hms(20, 0, 0),
+            duration: 120,
+        },
+        location_id,
+        status: None,
+        overrides: None,
+        audit: None,
+    }
+}
+
+/// An occurrence at a location that does not exist, so inserting it violates a foreign key.
+fn failing_occurrence() -> OccurrenceWithLocation {
+    occurrence(31, Uuid::new_v4().into())
+}
+
+fn event(title: &str, occurrences: Vec<OccurrenceWithLocation>) -> EventWithOccurrences {
+    EventWithOccurrences {
+        event: Event {
+            title: title.to_string(),
+            teaser: "Tanzen".to_string(),
+            description: "Social Dance".to_string(),
+            price: None,
+            category: None,
+            tags: None,
+            publication: None,
+            audit: None,
+        },
+        occurrences,
+        recurrences: None,
+        translations: None,
+    }
+}
+
+#[test]
+fn failed_create_stores_nothing() {
+    let store = store();
+    let location_id = location(&store);
+    let before = snapshot(&store);
+
+    let result = store.create_event_with_occurrences(event(
+        "Lindy Hop Social",
+        vec![occurrence(1, location_id), failing_occurrence()],
+    ));
+
+    assert!(result.is_err());
+    assert_eq!(snapshot(&store), before);
+}
+
+#[test]
+fn failed_update_keeps_event_and_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let before = snapshot(&store);
+
+    let result = store.update_event_with_occurrences(
+        event_id,
+        event(
+            "Balboa Social",
+            vec![occurrence(8, location_id), failing_occurrence()],
+        ),
+        &OccurrenceFilter::default(),
+    );
+
+    assert!(result.is_err());
+    assert_eq!(snapshot(&store), before);
+}
+
+#[test]
+fn failed_add_occurrences_adds_none() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let before = snapshot(&store);
+
+    let result = store.add_occurrences(
+        event_id,
+        vec![occurrence(8, location_id), failing_occurrence()],
+        vec![],
+    );
+
+    assert!(result.is_err());
+    assert_eq!(snapshot(&store), before);
+}
+
+#[test]
+fn refused_location_deletion_keeps_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let before = snapshot(&store);
+
+    let result = store.delete_location(location_id, LocationDeletion::Refuse);
+
+    assert!(result.is_err());
+    assert_eq!(snapshot(&store), before);
+}
+
+#[test]
+fn update_keeps_ids_of_unchanged_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![
+                occurrence(1, location_id.clone()),
+                occurrence(8, location_id.clone()),
+            ],
+        ))
+        .expect("Creating the event failed.");
+    let stored = store
+        .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+        .expect("Reading the event failed.")
+        .occurrences;
+
+    // The first occurrence is sent without its id, the second one is moved.
+    let moved = OccurrenceWithLocation {
+        id: stored[1].id.clone(),
+        ..occurrence(15, location_id.clone())
+    };
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event(
+                "Lindy Hop Social",
+                vec![occurrence(1, location_id.clone()), moved],
+            ),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+
+    let updated = store
+        .read_event_with_occurrences(event_id, &OccurrenceFilter::default())
+        .expect("Reading the event failed.")
+        .occurrences;
+    assert_eq!(updated.len(), 2);
+    assert_eq!(updated[0].id, stored[0].id);
+    assert_eq!(updated[1].id, stored[1].id);
+    assert_eq!(
+        updated[1].occurrence,
+        occurrence(15, location_id).occurrence
+    );
+}
+
+#[test]
+fn update_without_status_keeps_cancellation() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let stored = store
+        .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+        .expect("Reading the event failed.")
+        .occurrences;
+    let cancelled = OccurrenceStatus {
+        state: OccurrenceState::Cancelled,
+        note: "Der Saal ist belegt.".to_string(),
+    };
+    store
+        .set_occurrence_status(
+            event_id.clone(),
+            stored[0]
+                .id
+                .clone()
+                .expect("Stored occurrences have an id."),
+            cancelled.clone(),
+        )
+        .expect("Setting the status failed.");
+
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event("Lindy Hop Social", vec![occurrence(1, location_id)]),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+
+    let updated = store
+        .read_event_with_occurrences(event_id, &OccurrenceFilter::default())
+        .expect("Reading the event failed.")
+        .occurrences;
+    assert_eq!(updated[0].id, stored[0].id);
+    assert_eq!(updated[0].status, Some(cancelled));
+}
+
+#[test]
+fn restore_deleted_event_with_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![
+                occurrence(1, location_id.clone()),
+                occurrence(8, location_id),
+            ],
+        ))
+        .expect("Creating the event failed.");
+    let stored = store
+        .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+        .expect("Reading the event failed.");
+
+    store
+        .delete_event_with_occurrences(event_id.clone())
+        .expect("Deleting the event failed.");
+    let history = store
+        .event_history(event_id.clone())
+        .expect("Reading the history failed.");
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].1.action, RevisionAction::Delete);
+
+    let restored = store
+        .restore_event(event_id, history[0].0.clone())
+        .expect("Restoring the event failed.");
+    assert_eq!(restored.event.title, stored.event.title);
+    assert_eq!(
+        restored
+            .occurrences
+            .iter()
+            .map(|occurrence| occurrence.id.clone())
+            .collect::<Vec<_>>(),
+        stored
+            .occurrences
+            .iter()
+            .map(|occurrence| occurrence.id.clone())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn trashed_event_is_hidden_until_restored() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event("Lindy Hop Social", vec![occurrence(1, location_id)]))
+        .expect("Creating the event failed.");
+
+    store
+        .delete_event_with_occurrences(event_id.clone())
+        .expect("Deleting the event failed.");
+    assert!(store
+        .all_events_with_occurrences(&OccurrenceFilter::default())
+        .is_empty());
+    assert!(store
+        .trash()
+        .expect("Reading the trash failed.")
+        .events
+        .contains_key(&event_id));
+
+    let restored = store
+        .restore_trashed_event(event_id.clone())
+        .expect("Restoring the event failed.");
+    assert_eq!(restored.occurrences.len(), 1);
+    assert!(store
+        .all_events_with_occurrences(&OccurrenceFilter::default())
+        .contains_key(&event_id));
+}
+
+#[test]
+fn drafts_are_only_listed_with_unpublished_events() {
+    let store = store();
+    let location_id = location(&store);
+    let mut draft = event("Lindy Hop Social", vec![occurrence(1, location_id)]);
+    draft.event.publication = Some(Publication::Draft);
+    let event_id = store
+        .create_event_with_occurrences(draft)
+        .expect("Creating the event failed.");
+
+    assert!(store
+        .occurrences_by_date(&OccurrenceFilter::default())
+        .is_empty());
+    let with_unpublished = OccurrenceFilter {
+        unpublished: true,
+        ..OccurrenceFilter::default()
+    };
+    assert_eq!(store.occurrences_by_date(&with_unpublished).len(), 1);
+
+    // Omitting the publication on update keeps the draft unpublished.
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event("Lindy Hop Social", vec![]),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert!(!store
+        .event_is_published(event_id)
+        .expect("Reading the event failed."));
+}
+
+#[test]
+fn drafts_are_new_from_when_they_are_published() {
+    let store = store();
+    let mut draft = event("Lindy Hop Social", vec![]);
+    draft.event.publication = Some(Publication::Draft);
+    let event_id = store
+        .create_event_with_occurrences(draft)
+        .expect("Creating the event failed.");
+    let since = db::now();
+    assert!(store.events_published_since(since).is_empty());
+
+    let published = || {
+        let mut published = event("Lindy Hop Social", vec![]);
+        published.event.publication = Some(Publication::Published);
+        published
+    };
+    store
+        .update_event_with_occurrences(event_id.clone(), published(), &OccurrenceFilter::default())
+        .expect("Publishing the event failed.");
+    let new_events = store.events_published_since(since);
+    assert_eq!(new_events.len(), 1);
+    assert_eq!(new_events[0].0, event_id);
+    let published_at = new_events[0]
+        .1
+        .audit
+        .as_ref()
+        .and_then(|audit| audit.published_at);
+    assert!(published_at > Some(since));
+
+    // Saving it again does not make it new again.
+    store
+        .update_event_with_occurrences(event_id, published(), &OccurrenceFilter::default())
+        .expect("Updating the event failed.");
+    let new_events = store.events_published_since(since);
+    assert_eq!(
+        new_events[0]
+            .1
+            .audit
+            .as_ref()
+            .and_then(|audit| audit.published_at),
+        published_at
+    );
+}
+
+#[test]
+fn null_price_is_told_apart_from_omitted_one() {
+    let omitted: Event = serde_json::from_value(json!({
+        "title": "Lindy Hop Social",
+        "teaser": "Tanzen",
+        "description": "Social Dance",
+    }))
+    .expect("Invalid event.");
+    let removed: Event = serde_json::from_value(json!({
+        "title": "Lindy Hop Social",
+        "teaser": "Tanzen",
+        "description": "Social Dance",
+        "price": null,
+    }))
+    .expect("Invalid event.");
+
+    assert_eq!(omitted.price, None);
+    assert_eq!(removed.price, Some(None));
+}
+
+#[test]
+fn update_keeps_omitted_price_and_removes_null_one() {
+    let store = store();
+    let location_id = location(&store);
+    let free = Price {
+        kind: PriceKind::Free,
+        note: String::new(),
+    };
+    let mut priced = event("Lindy Hop Social", vec![occurrence(1, location_id)]);
+    priced.event.price = Some(Some(free.clone()));
+    let event_id = store
+        .create_event_with_occurrences(priced)
+        .expect("Creating the event failed.");
+    let read_price = || {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+            .event
+            .current_price()
+            .cloned()
+    };
+
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event("Lindy Hop Social", vec![]),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert_eq!(read_price(), Some(free));
+
+    let mut without_price = event("Lindy Hop Social", vec![]);
+    without_price.event.price = Some(None);
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            without_price,
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert_eq!(read_price(), None);
+}
+
+#[test]
+fn update_keeps_omitted_category_and_removes_null_one() {
+    let store = store();
+    let location_id = location(&store);
+    let mut social = event("Lindy Hop Social", vec![occurrence(1, location_id)]);
+    social.event.category = Some(Some(Category::Social));
+    let event_id = store
+        .create_event_with_occurrences(social)
+        .expect("Creating the event failed.");
+    let read_category = || {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+            .event
+            .current_category()
+    };
+
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event("Lindy Hop Social", vec![]),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert_eq!(read_category(), Some(Category::Social));
+
+    let mut without_category = event("Lindy Hop Social", vec![]);
+    without_category.event.category = Some(None);
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            without_category,
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert_eq!(read_category(), None);
+}
+
+fn weekly(location_id: Id<Location>) -> Recurrence {
+    Recurrence {
+        id: None,
+        first: FirstOccurrence {
+            occurrence: occurrence(1, location_id.clone()).occurrence,
+            location_id,
+        },
+        frequency: Frequency::Weekly,
+        until: Some(NaiveDate::from_ymd(2019, 7, 29)),
+        count: None,
+        exceptions: vec![],
+    }
+}
+
+#[test]
+fn update_keeps_ids_of_recurrence_rules_and_their_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(EventWithOccurrences {
+            recurrences: Some(vec![weekly(location_id.clone())]),
+            ..event("Lindy Hop Social", vec![])
+        })
+        .expect("Creating the event failed.");
+    let read = || {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+    };
+    let occurrence_ids = |item: &EventWithOccurrences| {
+        item.occurrences
+            .iter()
+            .map(|occurrence| occurrence.id.clone())
+            .collect::<Vec<_>>()
+    };
+    let stored = read();
+    let rule = stored.recurrences.clone().unwrap_or_default()[0].clone();
+    assert!(rule.id.is_some());
+    assert_eq!(stored.occurrences.len(), 5);
+
+    // Resending the identical rule without its id keeps it.
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            EventWithOccurrences {
+                recurrences: Some(vec![weekly(location_id)]),
+                ..event("Lindy Hop Social", vec![])
+            },
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    assert_eq!(occurrence_ids(&read()), occurrence_ids(&stored));
+
+    // A changed rule sent with its id keeps it as well.
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            EventWithOccurrences {
+                recurrences: Some(vec![Recurrence {
+                    until: Some(NaiveDate::from_ymd(2019, 7, 22)),
+                    ..rule.clone()
+                }]),
+                ..event("Lindy Hop Social", vec![])
+            },
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+    let updated = read();
+    assert_eq!(
+        updated.recurrences.clone().unwrap_or_default()[0].id,
+        rule.id
+    );
+    assert_eq!(
+        occurrence_ids(&updated),
+        occurrence_ids(&stored)[..4].to_vec()
+    );
+}
+
+#[test]
+fn update_gives_occurrences_with_unknown_ids_new_ones() {
+    let store = store();
+    let location_id = location(&store);
+    let read = |event_id: &Id<Event>| {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+    };
+    let blues_id = store
+        .create_event_with_occurrences(event(
+            "Blues Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let lindy_id = store
+        .create_event_with_occurrences(event("Lindy Hop Social", vec![]))
+        .expect("Creating the event failed.");
+    let blues_occurrence = read(&blues_id).occurrences[0].clone();
+
+    // Sending the occurrence of another event must not move or collide with it.
+    store
+        .update_event_with_occurrences(
+            lindy_id.clone(),
+            event(
+                "Lindy Hop Social",
+                vec![OccurrenceWithLocation {
+                    id: blues_occurrence.id.clone(),
+                    ..occurrence(8, location_id)
+                }],
+            ),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+
+    let lindy_occurrences = read(&lindy_id).occurrences;
+    assert_eq!(lindy_occurrences.len(), 1);
+    assert_ne!(lindy_occurrences[0].id, blues_occurrence.id);
+    assert_eq!(read(&blues_id).occurrences[0], blues_occurrence);
+}
+
+/// The invalid fields with their messages, or the database error.
+fn validation_errors<T: Validate>(store: &Store, item: &T) -> serde_json::Value {
+    match item.validate(store) {
+        Ok(()) => json!({}),
+        Err(ValidationError::Invalid(errors)) => json!(errors),
+        Err(ValidationError::Database(err)) => panic!("Validating failed: {}", err),
+    }
+}
+
+#[test]
+fn validation_prefixes_nested_fields_with_their_path() {
+    let store = store();
+    let location_id = location(&store);
+    let mut invalid = event("", vec![occurrence(1, location_id.clone())]);
+    invalid.event.tags = Some(vec!["Swing".to_string()]);
+    invalid.occurrences.push(OccurrenceWithLocation {
+        occurrence: Occurrence {
+            duration: 0,
+            ..occurrence(8, location_id).occurrence
+        },
+        ..occurrence(8, Uuid::new_v4().into())
+    });
+
+    assert_eq!(
+        validation_errors(&store, &invalid),
+        json!({
+            "event.title": ["Darf nicht leer sein."],
+            "event.tags.0": ["Darf nur Kleinbuchstaben, Ziffern und Bindestriche enthalten."],
+            "occurrences.1.duration": ["Die Dauer muss größer als 0 sein."],
+            "occurrences.1.location_id": ["Der gewählte Ort konnte nicht gefunden werden."],
+        })
+    );
+}
+
+#[test]
+fn validation_allows_empty_teaser_description_and_address() {
+    let store = store();
+    let mut minimal = event("Lindy Hop Social", vec![]);
+    minimal.event.teaser = String::new();
+    minimal.event.description = String::new();
+    let location = Location {
+        name: "Chico Mendes".to_string(),
+        address: String::new(),
+        audit: None,
+    };
+
+    assert_eq!(validation_errors(&store, &minimal), json!({}));
+    assert_eq!(validation_errors(&store, &location), json!({}));
+}
+
+#[test]
+fn validation_limits_the_duration_to_a_day() {
+    let store = store();
+    let location_id = location(&store);
+    let lasting = |duration| OccurrenceWithLocation {
+        occurrence: Occurrence {
+            duration,
+            ..occurrence(1, location_id.clone()).occurrence
+        },
+        ..occurrence(1, location_id.clone())
+    };
+
+    assert_eq!(validation_errors(&store, &lasting(24 * 60)), json!({}));
+    assert_eq!(
+        validation_errors(&store, &lasting(24 * 60 + 1)),
+        json!({ "duration": ["Die Dauer darf höchstens 24 Stunden betragen."] })
+    );
+}
+
+#[test]
+fn validation_fails_if_the_location_cannot_be_looked_up() {
+    let store = store();
+    let location_id = location(&store);
+    diesel::sql_query("DROP TABLE locations")
+        .execute(&*store.0)
+        .expect("Dropping the locations failed.");
+
+    assert!(matches!(
+        occurrence(1, location_id).validate(&store),
+        Err(ValidationError::Database(_))
+    ));
+}
+
+#[test]
+fn validation_reserves_language_codes_as_slugs() {
+    let store = store();
+    let page = |slug: &str| Page {
+        slug: slug.to_string(),
+        title: "Kurse".to_string(),
+        body: String::new(),
+        published: true,
+        audit: None,
+    };
+    let taken = json!({ "slug": ["Diese Adresse ist bereits vergeben."] });
+
+    for locale in Locale::ALL.iter() {
+        assert_eq!(validation_errors(&store, &page(locale.code())), taken);
+    }
+    assert_eq!(validation_errors(&store, &page("admin")), taken);
+    assert_eq!(validation_errors(&store, &page("kurse")), json!({}));
+}
+
+#[test]
+fn validation_requires_passwords_of_a_minimum_length() {
+    let store = store();
+    let acceptance = |password: &str| Acceptance {
+        token: "token".to_string(),
+        password: password.to_string(),
+    };
+    let too_short = json!({ "password": ["Muss mindestens 8 Zeichen lang sein."] });
+
+    assert_eq!(validation_errors(&store, &acceptance("")), too_short);
+    assert_eq!(
+        validation_errors(&store, &acceptance("          ")),
+        too_short
+    );
+    assert_eq!(validation_errors(&store, &acceptance("swing")), too_short);
+    assert_eq!(
+        validation_errors(&store, &acceptance("swingout")),
+        json!({})
+    );
+}
+
+#[test]
+fn purge_trash_keeps_locations_of_trashed_events_until_they_are_purged() {
+    use db::schema::locations::dsl::{deleted_at, locations};
+
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    store
+        .delete_event_with_occurrences(event_id.clone())
+        .expect("Deleting the event failed.");
+    store
+        .delete_location(location_id.clone(), LocationDeletion::Refuse)
+        .expect("Deleting the location failed.");
+    // The location has expired, the event has not.
+    let long_ago = NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0);
+    diesel::update(locations.find(db::SqlId::from(location_id.clone())))
+        .set(deleted_at.eq(Some(long_ago)))
+        .execute(&*store.0)
+        .expect("Backdating the deletion failed.");
+
+    store
+        .purge_trash(long_ago + chrono::Duration::days(1))
+        .expect("Purging the trash failed.");
+    let trash = store.trash().expect("Reading the trash failed.");
+    assert!(trash.events.contains_key(&event_id));
+    assert!(trash.locations.contains_key(&location_id));
+
+    store
+        .purge_trash(db::now() + chrono::Duration::days(1))
+        .expect("Purging the trash failed.");
+    let trash = store.trash().expect("Reading the trash failed.");
+    assert!(trash.events.is_empty());
+    assert!(trash.locations.is_empty());
+}
+
+#[test]
+fn deleting_a_location_keeps_occurrences_of_trashed_events() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let trashed = store
+        .delete_event_with_occurrences(event_id.clone())
+        .expect("Deleting the event failed.");
+
+    store
+        .delete_location(location_id.clone(), LocationDeletion::Cascade)
+        .expect("Deleting the location failed.");
+    let restored = store
+        .restore_trashed_event(event_id)
+        .expect("Restoring the event failed.");
+
+    assert_eq!(restored.occurrences, trashed.occurrences);
+    assert!(store.location_exists(location_id).unwrap_or(false));
+}
+
+#[test]
+fn update_keeps_omitted_overrides_and_status() {
+    let store = store();
+    let location_id = location(&store);
+    let live_band = OccurrenceWithLocation {
+        status: Some(OccurrenceStatus {
+            state: OccurrenceState::Tentative,
+            note: "Falls die Band kann".to_string(),
+        }),
+        overrides: Some(OccurrenceOverrides {
+            title_suffix: "mit Live-Band".to_string(),
+            teaser: "Tanzen zur Musik der Band".to_string(),
+            note: String::new(),
+        }),
+        ..occurrence(1, location_id.clone())
+    };
+    let event_id = store
+        .create_event_with_occurrences(event("Lindy Hop Social", vec![live_band.clone()]))
+        .expect("Creating the event failed.");
+
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            event("Lindy Hop Social", vec![occurrence(1, location_id)]),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+
+    let updated = store
+        .read_event_with_occurrences(event_id, &OccurrenceFilter::default())
+        .expect("Reading the event failed.");
+    assert_eq!(
+        updated.occurrences[0].current_status(),
+        live_band.current_status()
+    );
+    assert_eq!(
+        updated.occurrences[0].current_overrides(),
+        live_band.current_overrides()
+    );
+}
+
+#[test]
+fn update_occurrence_keeps_its_creation_time() {
+    use db::schema::occurrences::dsl::{created_at, occurrences};
+
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(event(
+            "Lindy Hop Social",
+            vec![occurrence(1, location_id.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let read = || {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+            .occurrences[0]
+            .clone()
+    };
+    let occurrence_id: Id<Occurrence> = read().id.expect("Stored occurrences have an id.");
+    let long_ago = NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0);
+    diesel::update(occurrences.find(db::SqlId::from(occurrence_id.clone())))
+        .set(created_at.eq(long_ago))
+        .execute(&*store.0)
+        .expect("Backdating the creation failed.");
+
+    store
+        .update_occurrence(
+            event_id.clone(),
+            occurrence_id.clone(),
+            occurrence(8, location_id),
+        )
+        .expect("Updating the occurrence failed.");
+
+    let updated = read();
+    assert_eq!(updated.id, Some(occurrence_id));
+    assert_eq!(
+        updated.occurrence.start.date(),
+        NaiveDate::from_ymd(2019, 7, 8)
+    );
+    let audit = updated.audit.expect("Stored occurrences have an audit.");
+    assert_eq!(audit.created_at, long_ago);
+    assert!(audit.updated_at > long_ago);
+}
+
+#[test]
+fn revisions_only_keep_stored_occurrences() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(EventWithOccurrences {
+            recurrences: Some(vec![weekly(location_id.clone())]),
+            ..event("Lindy Hop Social", vec![occurrence(3, location_id.clone())])
+        })
+        .expect("Creating the event failed.");
+
+    store
+        .delete_event_with_occurrences(event_id.clone())
+        .expect("Deleting the event failed.");
+
+    let history = store
+        .event_history(event_id)
+        .expect("Reading the history failed.");
+    let snapshot = &history[0].1.snapshot;
+    assert_eq!(snapshot["occurrences"].as_array().map(Vec::len), Some(1));
+    assert_eq!(snapshot["recurrences"].as_array().map(Vec::len), Some(1));
+}
+
+#[test]
+fn restoring_a_purged_location_keeps_its_creation_time() {
+    use db::schema::locations::dsl::{created_at, locations};
+
+    let store = store();
+    let location_id = location(&store);
+    let long_ago = NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0);
+    diesel::update(locations.find(db::SqlId::from(location_id.clone())))
+        .set(created_at.eq(long_ago))
+        .execute(&*store.0)
+        .expect("Backdating the creation failed.");
+    store
+        .delete_location(location_id.clone(), LocationDeletion::Refuse)
+        .expect("Deleting the location failed.");
+    store
+        .purge_location(location_id.clone())
+        .expect("Purging the location failed.");
+    let (revision_id, _) = store
+        .location_history(location_id.clone())
+        .expect("Reading the history failed.")
+        .remove(0);
+
+    let restored = store
+        .restore_location(location_id, revision_id)
+        .expect("Restoring the location failed.");
+
+    let audit = restored.audit.expect("Stored locations have an audit.");
+    assert_eq!(audit.created_at, long_ago);
+}
+
+#[test]
+fn rules_are_only_expanded_up_to_the_horizon() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(EventWithOccurrences {
+            recurrences: Some(vec![Recurrence {
+                until: None,
+                ..weekly(location_id)
+            }]),
+            ..event("Lindy Hop Social", vec![])
+        })
+        .expect("Creating the event failed.");
+    let far_future = OccurrenceFilter {
+        before: NaiveDate::from_ymd_opt(200_000, 1, 1).map(|date| date.and_hms(0, 0, 0)),
+        ..OccurrenceFilter::default()
+    };
+
+    let expanded = store
+        .read_event_with_occurrences(event_id, &far_future)
+        .expect("Reading the event failed.");
+
+    let horizon = chrono::Local::today().naive_local() + chrono::Duration::days(365);
+    let last = expanded
+        .occurrences
+        .iter()
+        .map(|occurrence| occurrence.occurrence.start.date())
+        .max();
+    assert!(last.map_or(false, |last| last <= horizon
+        && last > horizon - chrono::Duration::days(7)));
+}
+
+#[test]
+fn update_stores_generated_occurrences_with_their_own_status() {
+    let store = store();
+    let location_id = location(&store);
+    let event_id = store
+        .create_event_with_occurrences(EventWithOccurrences {
+            recurrences: Some(vec![weekly(location_id)]),
+            ..event("Lindy Hop Social", vec![])
+        })
+        .expect("Creating the event failed.");
+    let read = || {
+        store
+            .read_event_with_occurrences(event_id.clone(), &OccurrenceFilter::default())
+            .expect("Reading the event failed.")
+    };
+    let mut changed = read();
+    let cancelled = OccurrenceStatus {
+        state: OccurrenceState::Cancelled,
+        note: "Sommerpause".to_string(),
+    };
+    changed.occurrences[1].status = Some(cancelled.clone());
+
+    store
+        .update_event_with_occurrences(
+            event_id.clone(),
+            changed.clone(),
+            &OccurrenceFilter::default(),
+        )
+        .expect("Updating the event failed.");
+
+    let updated = read();
+    assert_eq!(updated.occurrences.len(), 5);
+    assert_eq!(updated.occurrences[1].id, changed.occurrences[1].id);
+    assert_eq!(updated.occurrences[1].current_status(), cancelled);
+    assert!(updated.occurrences[1].audit.is_some());
+    assert_eq!(
+        updated.recurrences.unwrap_or_default()[0].exceptions,
+        vec![NaiveDate::from_ymd(2019, 7, 8)]
+    );
+}
+
+#[test]
+fn occurrences_at_a_location_leave_out_other_locations() {
+    let store = store();
+    let here = location(&store);
+    let elsewhere = location(&store);
+    store
+        .create_event_with_occurrences(EventWithOccurrences {
+            recurrences: Some(vec![weekly(here.clone())]),
+            ..event("Lindy Hop Social", vec![occurrence(3, elsewhere.clone())])
+        })
+        .expect("Creating the event failed.");
+    store
+        .create_event_with_occurrences(event(
+            "Balboa Social",
+            vec![occurrence(5, elsewhere.clone())],
+        ))
+        .expect("Creating the event failed.");
+    let days = |location_id: &Id<Location>, filter: &OccurrenceFilter| {
+        store
+            .occurrences_by_date_at(location_id, filter)
+            .into_iter()
+            .flat_map(|(_, entries)| entries)
+            .map(|entry| (entry.occurrence.occurrence.start.day(), entry.event.title))
+            .collect::<Vec<_>>()
+    };
+
+    let social = "Lindy Hop Social".to_string();
+    assert_eq!(
+        days(&here, &OccurrenceFilter::default()),
+        vec![
+            (1, social.clone()),
+            (8, social.clone()),
+            (15, social.clone()),
+            (22, social.clone()),
+            (29, social.clone()),
+        ]
+    );
+    assert_eq!(
+        days(&elsewhere, &OccurrenceFilter::default()),
+        vec![(3, social.clone()), (5, "Balboa Social".to_string())]
+    );
+    let later = OccurrenceFilter {
+        after: Some(NaiveDate::from_ymd(2019, 7, 10).and_hms(0, 0, 0)),
+        ..OccurrenceFilter::default()
+    };
+    assert_eq!(
+        days(&here, &later),
+        vec![(15, social.clone()), (22, social.clone()), (29, social)]
+    );
+    assert!(days(&elsewhere, &later).is_empty());
+}
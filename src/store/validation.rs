@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::*;
+
+/// Paths of the website that pages cannot use as their slug. The language codes are reserved
+/// as well, since they prefix the pages' paths.
+const RESERVED_SLUGS: &[&str] = &[
+    "admin",
+    "api",
+    "events",
+    "invitation",
+    "locations",
+    "login",
+    "logout",
+    "static",
+];
+
+/// The shortest allowed password, in characters.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// The longest allowed occurrence. Longer events should be split into one occurrence per day.
+const MAX_DURATION: u32 = 24 * 60;
+
+/// Checks items received through the API before they are stored.
+pub trait Validate {
+    /// Records an error for each invalid field, prefixing the field names with `path`.
+    /// Only fails if the database cannot be queried.
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()>;
+
+    fn validate(&self, store: &Store) -> Result<(), ValidationError> {
+        let mut errors = ValidationErrors::default();
+        self.check(store, "", &mut errors)?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::Invalid(errors))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    Invalid(ValidationErrors),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ValidationError {
+    fn from(err: diesel::result::Error) -> Self {
+        ValidationError::Database(err)
+    }
+}
+
+/// The error messages for each invalid field. Nested fields are separated by dots,
+/// e. g. `occurrences.2.duration`.
+#[derive(Serialize, Debug, Default)]
+#[serde(transparent)]
+pub struct ValidationErrors(BTreeMap<String, Vec<String>>);
+
+impl ValidationErrors {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn add(&mut self, path: &str, field: &str, message: &str) {
+        self.0
+            .entry(join(path, field))
+            .or_default()
+            .push(message.to_string());
+    }
+
+    fn require(&mut self, path: &str, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(path, field, "Darf nicht leer sein.");
+        }
+    }
+}
+
+fn join(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+/// Slugs and tags only consist of lowercase letters, digits and dashes, e. g. `beginner-friendly`.
+fn is_slug(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+impl Validate for Event {
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        errors.require(path, "title", &self.title);
+        if let Some(price) = self.current_price() {
+            price.check(store, &join(path, "price"), errors)?;
+        }
+        for (index, tag) in self.tags.iter().flatten().enumerate() {
+            let (tags_path, field) = (join(path, "tags"), index.to_string());
+            if tag.is_empty() {
+                errors.add(&tags_path, &field, "Darf nicht leer sein.");
+            } else if !is_slug(tag) {
+                errors.add(
+                    &tags_path,
+                    &field,
+                    "Darf nur Kleinbuchstaben, Ziffern und Bindestriche enthalten.",
+                );
+            } else if self
+                .tags
+                .iter()
+                .flatten()
+                .take(index)
+                .any(|other| other == tag)
+            {
+                errors.add(&tags_path, &field, "Kommt bereits vor.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for Price {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        let check_currency = |currency: &str, errors: &mut ValidationErrors| {
+            if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+                errors.add(
+                    path,
+                    "currency",
+                    "Muss ein Währungscode aus drei Großbuchstaben sein, z. B. EUR.",
+                );
+            }
+        };
+
+        match &self.kind {
+            PriceKind::Fixed { currency, .. } => check_currency(currency, errors),
+            PriceKind::Range { min, max, currency } => {
+                if min > max {
+                    errors.add(
+                        path,
+                        "max",
+                        "Der Höchstpreis darf nicht unter dem Mindestpreis liegen.",
+                    );
+                }
+                check_currency(currency, errors);
+            }
+            PriceKind::Free | PriceKind::Donation | PriceKind::Consumption => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for Location {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        errors.require(path, "name", &self.name);
+
+        Ok(())
+    }
+}
+
+impl Validate for User {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        errors.require(path, "name", &self.name);
+
+        Ok(())
+    }
+}
+
+impl Validate for Acceptance {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        if self.password.trim().chars().count() < MIN_PASSWORD_LENGTH {
+            errors.add(
+                path,
+                "password",
+                &format!("Muss mindestens {} Zeichen lang sein.", MIN_PASSWORD_LENGTH),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for Page {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        errors.require(path, "title", &self.title);
+
+        if self.slug.is_empty() {
+            errors.add(path, "slug", "Darf nicht leer sein.");
+        } else if !is_slug(&self.slug) {
+            errors.add(
+                path,
+                "slug",
+                "Darf nur Kleinbuchstaben, Ziffern und Bindestriche enthalten.",
+            );
+        } else if RESERVED_SLUGS.contains(&self.slug.as_str())
+            || Locale::from_code(&self.slug).is_some()
+        {
+            errors.add(path, "slug", "Diese Adresse ist bereits vergeben.");
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for Occurrence {
+    fn check(&self, _: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        if self.duration == 0 {
+            errors.add(path, "duration", "Die Dauer muss größer als 0 sein.");
+        } else if self.duration > MAX_DURATION {
+            errors.add(
+                path,
+                "duration",
+                "Die Dauer darf höchstens 24 Stunden betragen.",
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn check_location(
+    store: &Store,
+    location_id: &Id<Location>,
+    path: &str,
+    errors: &mut ValidationErrors,
+) -> QueryResult<()> {
+    if !store.location_exists(location_id.clone())? {
+        errors.add(
+            path,
+            "location_id",
+            "Der gewählte Ort konnte nicht gefunden werden.",
+        );
+    }
+
+    Ok(())
+}
+
+impl Validate for OccurrenceWithLocation {
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        // The occurrence's fields are flattened into this one.
+        self.occurrence.check(store, path, errors)?;
+        check_location(store, &self.location_id, path, errors)
+    }
+}
+
+impl Validate for FirstOccurrence {
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        self.occurrence.check(store, path, errors)?;
+        check_location(store, &self.location_id, path, errors)
+    }
+}
+
+impl Validate for Recurrence {
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        self.first.check(store, path, errors)?;
+
+        if let Frequency::MonthlyNthWeekday { nth } = self.frequency {
+            if nth == 0 || nth.abs() > 5 {
+                errors.add(
+                    path,
+                    "frequency",
+                    "Die Woche im Monat muss zwischen -5 und 5 liegen und darf nicht 0 sein.",
+                );
+            }
+        }
+        if let Some(until) = self.until {
+            if until < self.first.occurrence.start.date() {
+                errors.add(
+                    path,
+                    "until",
+                    "Das Ende darf nicht vor dem ersten Termin liegen.",
+                );
+            }
+        }
+        if self.count == Some(0) {
+            errors.add(path, "count", "Die Anzahl muss größer als 0 sein.");
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for EventWithOccurrences {
+    fn check(&self, store: &Store, path: &str, errors: &mut ValidationErrors) -> QueryResult<()> {
+        self.event.check(store, &join(path, "event"), errors)?;
+
+        for (index, occurrence) in self.occurrences.iter().enumerate() {
+            let occurrence_path = join(&join(path, "occurrences"), &index.to_string());
+            occurrence.check(store, &occurrence_path, errors)?;
+        }
+
+        for (index, recurrence) in self.recurrences.iter().flatten().enumerate() {
+            let recurrence_path = join(&join(path, "recurrences"), &index.to_string());
+            recurrence.check(store, &recurrence_path, errors)?;
+        }
+
+        if let Some(translations) = &self.translations {
+            if translations.contains_key(&Locale::default()) {
+                errors.add(
+                    &join(path, "translations"),
+                    Locale::default().code(),
+                    "Die Standardsprache wird direkt in der Veranstaltung eingetragen.",
+                );
+            }
+        }
+
+        Ok(())
+    }
+}